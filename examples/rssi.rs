@@ -25,6 +25,10 @@ fn main() -> Result<(), crazyradio::Error> {
 
     cr.set_channel(channels[0])?;
 
+    if !cr.supports_rssi() {
+        println!("This radio does not measure RSSI (requires Crazyradio 2.0 with firmware >= 5.3)");
+    }
+
     println!("Sending packets and displaying RSSI (press Ctrl+C to stop):");
     println!("Channel | RSSI (dBm) | Retries | Ack payload length");
     println!("--------|------------|---------|-------------------");
@@ -12,6 +12,9 @@ fn main() -> Result<(), crazyradio::Error> {
     pb.set_message("Running stress test...");
 
     println!("Opened Crazyradio with serial number: {}", cr.serial()?);
+    if !cr.ping()? {
+        println!("Warning: no device answers on channel 42, acks will be missing");
+    }
 
     let mut i = 0;
     loop {
@@ -5,7 +5,8 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
-use crate::{Crazyradio, Error, Result, UsbCommand};
+use crate::usb::UsbDevice;
+use crate::{Crazyradio, Error, Result, UsbCommand, REQUEST_TYPE_VENDOR_IN};
 
 /// A packet received in async sniffer mode, with owned payload.
 #[derive(Debug, Clone)]
@@ -38,7 +39,8 @@ pub struct SnifferReceiver {
 /// will return [`Error::SnifferSessionClosed`] on use.
 #[derive(Clone)]
 pub struct SnifferSender {
-    device_handle: Arc<rusb::DeviceHandle<rusb::GlobalContext>>,
+    device_handle: Arc<dyn UsbDevice>,
+    endpoint_out: u8,
     session_active: Arc<AtomicBool>,
     #[cfg(feature = "packet_capture")]
     channel: u8,
@@ -53,10 +55,8 @@ impl SnifferReceiver {
     /// thread exited due to a USB error or after [`close`](Self::close) was called).
     pub async fn recv(&self) -> Option<Result<ReceivedSnifferPacket>> {
         let rx = self.packet_rx.as_ref()?;
-        match rx.recv_async().await {
-            Ok(result) => Some(result),
-            Err(_) => None, // channel disconnected
-        }
+        // Err means the channel is disconnected
+        rx.recv_async().await.ok()
     }
 
     /// Close the sniffer session and recover the [`Crazyradio`].
@@ -105,6 +105,7 @@ impl SnifferSender {
         }
 
         let handle = self.device_handle.clone();
+        let endpoint_out = self.endpoint_out;
         let mut buf = Vec::with_capacity(5 + data.len());
         buf.extend_from_slice(address);
         buf.extend_from_slice(data);
@@ -126,7 +127,7 @@ impl SnifferSender {
             );
 
             let result = handle
-                .write_bulk(0x01, &buf, Duration::from_secs(1))
+                .write_bulk(endpoint_out, &buf, Duration::from_secs(1))
                 .map(|_| ())
                 .map_err(Error::from);
             let _ = tx.send(result);
@@ -148,7 +149,7 @@ impl SnifferSender {
             let mut buf = [0u8; 4];
             let result = handle
                 .read_control(
-                    0xC0,
+                    REQUEST_TYPE_VENDOR_IN,
                     UsbCommand::GetSnifferDropCount as u8,
                     0,
                     0,
@@ -214,6 +215,7 @@ pub(crate) async fn enter_sniffer_mode_async(
 ) -> Result<(SnifferReceiver, SnifferSender)> {
     // Capture state needed for the sender before moving cr into the thread
     let device_handle = cr.device_handle.clone();
+    let endpoint_out = cr.endpoint_out;
 
     #[cfg(feature = "packet_capture")]
     let channel: u8 = cr.channel.into();
@@ -258,6 +260,7 @@ pub(crate) async fn enter_sniffer_mode_async(
 
     let sender = SnifferSender {
         device_handle,
+        endpoint_out,
         session_active,
         #[cfg(feature = "packet_capture")]
         channel,
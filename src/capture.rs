@@ -2,8 +2,19 @@
 //!
 //! This module provides a callback mechanism for capturing packets
 //! sent and received via the Crazyradio.
+//!
+//! The callback is stored in a [OnceLock]: once set, it is read without any
+//! lock, so capturing packets does not add contention between radios used
+//! from different threads. The callback can be called concurrently from
+//! several threads, which is why it must be `Sync`.
 
-use std::sync::OnceLock;
+use std::collections::VecDeque;
+#[cfg(feature = "capture_json")]
+use std::io::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+#[cfg(feature = "capture_json")]
+use std::time::UNIX_EPOCH;
 
 /// Direction: transmit (to device)
 pub const DIRECTION_TX: u8 = 0;
@@ -53,3 +64,239 @@ pub(crate) fn capture_packet(direction: u8, channel: u8, address: &[u8; 5], seri
         });
     }
 }
+
+/// Captured packet with owned data, as stored by [RingBufferSink]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedPacket {
+    /// Time at which the packet was captured
+    pub timestamp: SystemTime,
+    /// Packet direction: [`DIRECTION_TX`] or [`DIRECTION_RX`]
+    pub direction: u8,
+    /// Radio channel (0-125)
+    pub channel: u8,
+    /// 5-byte radio address
+    pub address: [u8; 5],
+    /// Serial number of the radio device
+    pub serial: String,
+    /// Packet payload data
+    pub data: Vec<u8>,
+}
+
+/// In-memory history of the most recent captured packets
+///
+/// Lighter-weight alternative to writing a capture file, for example to show
+/// the last packets in a user interface. The sink keeps the latest `capacity`
+/// packets, older packets are dropped. Clones share the same history.
+///
+/// Example:
+/// ```no_run
+/// use crazyradio::capture::{self, RingBufferSink};
+///
+/// let history = RingBufferSink::new(100);
+/// capture::set_callback(history.callback());
+///
+/// // ... use the radio ...
+///
+/// for packet in history.snapshot() {
+///     println!("{:?}", packet);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RingBufferSink {
+    capacity: usize,
+    packets: Arc<Mutex<VecDeque<CapturedPacket>>>,
+}
+
+impl RingBufferSink {
+    /// Create an empty sink keeping up to `capacity` packets
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            packets: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    /// Capture callback storing the packets in this sink, to pass to [set_callback()]
+    pub fn callback(&self) -> CaptureCallback {
+        let sink = self.clone();
+        Box::new(move |event| sink.push(event))
+    }
+
+    /// Store a packet, dropping the oldest one if the sink is full
+    pub fn push(&self, event: CaptureEvent<'_>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut packets = self.packets.lock().unwrap();
+        if packets.len() == self.capacity {
+            packets.pop_front();
+        }
+        packets.push_back(CapturedPacket {
+            timestamp: SystemTime::now(),
+            direction: event.direction,
+            channel: event.channel,
+            address: *event.address,
+            serial: event.serial.to_owned(),
+            data: event.data.to_vec(),
+        });
+    }
+
+    /// Copy of the stored packets, oldest first
+    pub fn snapshot(&self) -> Vec<CapturedPacket> {
+        self.packets.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// Capture sink writing each packet as one line of JSON
+///
+/// Each packet is written as a JSON object on its own line, for log analysis
+/// tools consuming JSON lines:
+///
+/// ```text
+/// {"timestamp_us":1700000000000000,"direction":"tx","channel":80,"address":"E7E7E7E7E7","serial":"FD61E54B7A","data":"FF"}
+/// ```
+///
+/// `timestamp_us` is the capture time in microseconds since the Unix epoch,
+/// `direction` is `tx` or `rx`, the address and the data are hexadecimal
+/// strings. The writer is flushed after each line.
+///
+/// Example:
+/// ```no_run
+/// use crazyradio::capture::{self, JsonlSink};
+///
+/// let file = std::fs::File::create("capture.jsonl").unwrap();
+/// capture::set_callback(JsonlSink::new(file).into_callback());
+/// ```
+#[cfg(feature = "capture_json")]
+#[cfg_attr(docsrs, doc(cfg(feature = "capture_json")))]
+#[derive(Debug)]
+pub struct JsonlSink<W> {
+    writer: Mutex<W>,
+}
+
+#[cfg(feature = "capture_json")]
+impl<W: Write> JsonlSink<W> {
+    /// Create a sink writing to `writer`
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Capture callback writing the packets to this sink, to pass to [set_callback()]
+    ///
+    /// Write errors are ignored since the callback cannot report them.
+    pub fn into_callback(self) -> CaptureCallback
+    where
+        W: Send + 'static,
+    {
+        Box::new(move |event| {
+            let _ = self.write(&event);
+        })
+    }
+
+    /// Write a packet as one JSON line and flush the writer
+    pub fn write(&self, event: &CaptureEvent<'_>) -> std::io::Result<()> {
+        let timestamp_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|t| t.as_micros() as u64)
+            .unwrap_or(0);
+        let line = serde_json::json!({
+            "timestamp_us": timestamp_us,
+            "direction": if event.direction == DIRECTION_TX { "tx" } else { "rx" },
+            "channel": event.channel,
+            "address": to_hex(event.address),
+            "serial": event.serial,
+            "data": to_hex(event.data),
+        });
+
+        let mut writer = self.writer.lock().unwrap();
+        serde_json::to_writer(&mut *writer, &line)?;
+        writer.write_all(b"\n")?;
+        writer.flush()
+    }
+
+    /// Return the writer of the sink
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap()
+    }
+}
+
+#[cfg(feature = "capture_json")]
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn callback_is_called_for_each_packet() {
+        let captured = Arc::new(AtomicUsize::new(0));
+        let counter = captured.clone();
+        set_callback(Box::new(move |event| {
+            if event.direction == DIRECTION_TX && event.data == [0xff] {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        }));
+
+        for _ in 0..3 {
+            capture_packet(DIRECTION_TX, 42, &[0xe7; 5], "test", &[0xff]);
+        }
+
+        assert_eq!(captured.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn ring_buffer_keeps_latest_packets() {
+        let sink = RingBufferSink::new(3);
+        let callback = sink.callback();
+
+        for i in 0..5u8 {
+            callback(CaptureEvent {
+                direction: DIRECTION_RX,
+                channel: 42,
+                address: &[0xe7; 5],
+                serial: "test",
+                data: &[i],
+            });
+        }
+
+        let data: Vec<_> = sink.snapshot().into_iter().map(|p| p.data).collect();
+        assert_eq!(data, [[2], [3], [4]]);
+    }
+
+    #[cfg(feature = "capture_json")]
+    #[test]
+    fn jsonl_sink_writes_one_object_per_line() {
+        let sink = JsonlSink::new(vec![]);
+        for (direction, data) in [(DIRECTION_TX, &[0xff][..]), (DIRECTION_RX, &[0x01, 0xab])] {
+            sink.write(&CaptureEvent {
+                direction,
+                channel: 80,
+                address: &[0xe7; 5],
+                serial: "test",
+                data,
+            })
+            .unwrap();
+        }
+
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["direction"], "tx");
+        assert_eq!(lines[0]["data"], "FF");
+        assert_eq!(lines[1]["direction"], "rx");
+        assert_eq!(lines[1]["channel"], 80);
+        assert_eq!(lines[1]["address"], "E7E7E7E7E7");
+        assert_eq!(lines[1]["serial"], "test");
+        assert_eq!(lines[1]["data"], "01AB");
+        assert!(lines[1]["timestamp_us"].as_u64().unwrap() > 0);
+    }
+}
@@ -0,0 +1,195 @@
+//! CRTP packet framing helpers
+//!
+//! The Crazyflie uses CRTP (Crazy RealTime Protocol) on top of the radio
+//! link. Each packet starts with a one byte header containing the CRTP port
+//! (bits 7-4) and channel (bits 1-0). Bits 3-2 are reserved for the link
+//! layer and are left at zero by this module.
+
+use std::io::{self, BufRead, Read};
+
+use crate::{Channel, PacketTransport};
+
+/// Maximum CRTP port number
+pub const MAX_PORT: u8 = 0x0f;
+/// Maximum CRTP channel number
+pub const MAX_CHANNEL: u8 = 0x03;
+/// CRTP port of the Crazyflie console
+pub const CONSOLE_PORT: u8 = 0x00;
+
+/// Build the CRTP header byte for a `port` and `channel`
+///
+/// Bits of `port` and `channel` that do not fit in the header are ignored.
+pub fn header(port: u8, channel: u8) -> u8 {
+    ((port & MAX_PORT) << 4) | (channel & MAX_CHANNEL)
+}
+
+/// Frame `data` as a CRTP packet by prepending the header for `port` and `channel`
+///
+/// Example:
+/// ```
+/// let packet = crazyradio::crtp::frame(15, 0, &[0x01, 0x02]);
+/// assert_eq!(packet, vec![0xf0, 0x01, 0x02]);
+/// ```
+pub fn frame(port: u8, channel: u8, data: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(data.len() + 1);
+    packet.push(header(port, channel));
+    packet.extend_from_slice(data);
+    packet
+}
+
+/// Decode a CRTP header byte into its `(port, channel)`
+pub fn parse_header(byte: u8) -> (u8, u8) {
+    (byte >> 4, byte & MAX_CHANNEL)
+}
+
+/// Reader of the Crazyflie console text
+///
+/// The Crazyflie sends its console output in the ack payloads of the packets
+/// it receives, as CRTP packets on port [CONSOLE_PORT] channel 0. This reader
+/// sends null packets (`[0xff]`) to `address` on `channel` through any
+/// [PacketTransport], like a [Crazyradio](crate::Crazyradio) or a
+/// `SharedCrazyradio`, and returns the console bytes of the acks, the other
+/// acks are ignored. It implements [Read] and [BufRead], so that the console
+/// can be read line by line:
+///
+/// ```no_run
+/// use crazyradio::crtp::ConsoleReader;
+/// use std::io::BufRead;
+///
+/// # fn main() -> Result<(), crazyradio::Error> {
+/// let radio = crazyradio::Crazyradio::open_first()?;
+/// let channel = crazyradio::Channel::from_number(80)?;
+///
+/// for line in ConsoleReader::new(radio, channel, [0xe7; 5]).lines() {
+///     println!("{}", line.unwrap());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Reading blocks, sending packets continuously, until console bytes are
+/// received: the reader never reaches the end of file. Radio errors are
+/// returned as [io::Error] wrapping the [crate::Error].
+#[derive(Debug)]
+pub struct ConsoleReader<T> {
+    transport: T,
+    channel: Channel,
+    address: [u8; 5],
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+impl<T: PacketTransport> ConsoleReader<T> {
+    /// Read the console of the Crazyflie at `address` on `channel` through `transport`
+    pub fn new(transport: T, channel: Channel, address: [u8; 5]) -> Self {
+        Self {
+            transport,
+            channel,
+            address,
+            buffer: vec![],
+            position: 0,
+        }
+    }
+
+    /// Return the transport, the console bytes not read yet are dropped
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+}
+
+impl<T: PacketTransport> BufRead for ConsoleReader<T> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.position == self.buffer.len() {
+            let (ack, payload) = self
+                .transport
+                .send_packet(self.channel, self.address, &[0xff])
+                .map_err(io::Error::other)?;
+            if let (true, Some((&header, data))) = (ack.received, payload.split_first()) {
+                if parse_header(header) == (CONSOLE_PORT, 0) {
+                    self.buffer = data.to_vec();
+                    self.position = 0;
+                }
+            }
+        }
+        Ok(&self.buffer[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.buffer.len());
+    }
+}
+
+impl<T: PacketTransport> Read for ConsoleReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let length = available.len().min(buf.len());
+        buf[..length].copy_from_slice(&available[..length]);
+        self.consume(length);
+        Ok(length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Ack, Result};
+    use std::collections::VecDeque;
+
+    // Answers each packet with the next scripted ack payload
+    struct ScriptedTransport {
+        payloads: VecDeque<Vec<u8>>,
+    }
+
+    impl PacketTransport for ScriptedTransport {
+        fn send_packet(
+            &mut self,
+            _channel: Channel,
+            _address: [u8; 5],
+            _payload: &[u8],
+        ) -> Result<(Ack, Vec<u8>)> {
+            let payload = self.payloads.pop_front().unwrap_or_default();
+            let ack = Ack {
+                received: true,
+                power_detector: false,
+                retry: 0,
+                length: payload.len(),
+                rssi_dbm: None,
+            };
+            Ok((ack, payload))
+        }
+    }
+
+    #[test]
+    fn console_payloads_are_read_as_lines() {
+        let transport = ScriptedTransport {
+            payloads: VecDeque::from([
+                b"\x00Hel".to_vec(),
+                vec![0xf3],
+                vec![],
+                b"\x00lo\nwo".to_vec(),
+                b"\x20ignored".to_vec(),
+                b"\x00rld\n".to_vec(),
+            ]),
+        };
+        let channel = Channel::from_number(80).unwrap();
+        let console = ConsoleReader::new(transport, channel, [0xe7; 5]);
+
+        let lines: Vec<String> = console.lines().take(2).map(|l| l.unwrap()).collect();
+        assert_eq!(lines, ["Hello", "world"]);
+    }
+
+    #[test]
+    fn header_round_trips_for_all_ports_and_channels() {
+        for port in 0..=MAX_PORT {
+            for channel in 0..=MAX_CHANNEL {
+                assert_eq!(parse_header(header(port, channel)), (port, channel));
+            }
+        }
+    }
+
+    #[test]
+    fn link_bits_are_ignored_when_parsing() {
+        assert_eq!(parse_header(0xfc), (15, 0));
+        assert_eq!(frame(0, 0, &[0x42]), vec![0x00, 0x42]);
+    }
+}
@@ -0,0 +1,348 @@
+// Fake Crazyradio dongle to test the radio functions without hardware
+//
+// FakeDongle implements UsbDevice by emulating the firmware: the settings
+// sent by control request are applied, the packets are answered with an ack
+// if a device is configured on the current channel and datarate, and every
+// request and packet is recorded for the tests to check.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::usb::UsbDevice;
+use crate::{Channel, Crazyradio, Datarate, OpenOptions, Result, UsbCommand};
+
+#[derive(Default)]
+pub(crate) struct FakeDongle {
+    state: Mutex<FakeState>,
+}
+
+#[derive(Default)]
+struct FakeState {
+    firmware_version: u16,
+
+    // Radio settings as set by the control requests and the inline headers
+    channel: u8,
+    datarate: u16,
+    address: [u8; 5],
+    ack_enable: bool,
+    inline_mode: u16,
+    sniffer_mode: bool,
+
+    // (channel, datarate) on which a device acks, with its ack payload
+    devices: Vec<(u8, u16, Vec<u8>)>,
+    // Channels on which the power detector trips
+    noisy: Vec<u8>,
+    // Requests stalled as if they were not implemented by the firmware
+    stalled: Vec<u8>,
+    // Clearing the halt fails until the configuration is selected again
+    halted: bool,
+
+    requests: Vec<(u8, u16, Vec<u8>)>,
+    packets: Vec<Vec<u8>>,
+    answers: VecDeque<Vec<u8>>,
+    firmware_scan: Vec<u8>,
+    events: Vec<String>,
+}
+
+impl FakeDongle {
+    // Fake dongle running the firmware `version`, in BCD like the USB
+    // descriptor: 0x0053 for the Crazyradio PA 0.53, 0x0530 for the
+    // Crazyradio 2.0 5.3. Only the Crazyradio 2.0 implements inline mode.
+    pub(crate) fn new(version: u16) -> Arc<Self> {
+        let dongle = FakeDongle::default();
+        {
+            let mut state = dongle.state();
+            state.firmware_version = version;
+            state.ack_enable = true;
+            if version < 0x0500 {
+                state.stalled.push(UsbCommand::SetInlineMode as u8);
+            }
+        }
+        Arc::new(dongle)
+    }
+
+    // Crazyradio opened with the default options on `self`
+    pub(crate) fn open(self: &Arc<Self>) -> Result<Crazyradio> {
+        self.open_with(&OpenOptions::new())
+    }
+
+    pub(crate) fn open_with(self: &Arc<Self>, options: &OpenOptions) -> Result<Crazyradio> {
+        Crazyradio::from_usb(self.clone(), options)
+    }
+
+    fn state(&self) -> MutexGuard<'_, FakeState> {
+        self.state.lock().unwrap()
+    }
+
+    // Make a device ack on `channel` at `datarate` with `payload`
+    pub(crate) fn add_device(&self, channel: Channel, datarate: Datarate, payload: &[u8]) {
+        let mut state = self.state();
+        state
+            .devices
+            .push((channel.into(), datarate as u16, payload.to_vec()));
+    }
+
+    pub(crate) fn remove_devices(&self) {
+        self.state().devices.clear();
+    }
+
+    // Make the power detector trip on `channel`
+    pub(crate) fn add_noise(&self, channel: Channel) {
+        self.state().noisy.push(channel.into());
+    }
+
+    // Stall `command` like a firmware not implementing it
+    pub(crate) fn stall(&self, command: UsbCommand) {
+        self.state().stalled.push(command as u8);
+    }
+
+    // Stall the bulk endpoints until the configuration is selected again
+    pub(crate) fn halt(&self) {
+        self.state().halted = true;
+    }
+
+    // Queue an answer to be read from the bulk IN endpoint
+    pub(crate) fn push_answer(&self, answer: &[u8]) {
+        self.state().answers.push_back(answer.to_vec());
+    }
+
+    // Value of each `command` control request received, oldest first
+    pub(crate) fn values(&self, command: UsbCommand) -> Vec<u16> {
+        let command = command as u8;
+        let state = self.state();
+        state
+            .requests
+            .iter()
+            .filter(|(request, _, _)| *request == command)
+            .map(|(_, value, _)| *value)
+            .collect()
+    }
+
+    // Data of each `command` control request received, oldest first
+    pub(crate) fn data(&self, command: UsbCommand) -> Vec<Vec<u8>> {
+        let command = command as u8;
+        let state = self.state();
+        state
+            .requests
+            .iter()
+            .filter(|(request, _, _)| *request == command)
+            .map(|(_, _, data)| data.clone())
+            .collect()
+    }
+
+    // Number of control requests received
+    pub(crate) fn request_count(&self) -> usize {
+        self.state().requests.len()
+    }
+
+    // Data of the bulk OUT transfers received, oldest first
+    pub(crate) fn packets(&self) -> Vec<Vec<u8>> {
+        self.state().packets.clone()
+    }
+
+    // Endpoint and interface operations, like "clear_halt 0x81", oldest first
+    pub(crate) fn events(&self) -> Vec<String> {
+        self.state().events.clone()
+    }
+
+    pub(crate) fn channel(&self) -> Channel {
+        Channel(self.state().channel)
+    }
+
+    pub(crate) fn address(&self) -> [u8; 5] {
+        self.state().address
+    }
+}
+
+impl FakeState {
+    fn control(&mut self, request: u8, value: u16, index: u16, data: &[u8]) -> rusb::Result<()> {
+        if self.stalled.contains(&request) {
+            return Err(rusb::Error::Pipe);
+        }
+        self.requests.push((request, value, data.to_vec()));
+
+        match request {
+            r if r == UsbCommand::SetRadioChannel as u8 => self.channel = value as u8,
+            r if r == UsbCommand::SetDataRate as u8 => self.datarate = value,
+            r if r == UsbCommand::SetRadioAddress as u8 => self.address.copy_from_slice(data),
+            r if r == UsbCommand::AckEnable as u8 => self.ack_enable = value != 0,
+            r if r == UsbCommand::SetInlineMode as u8 => self.inline_mode = value,
+            r if r == UsbCommand::SetRadioMode as u8 => self.sniffer_mode = value == 1,
+            r if r == UsbCommand::ScanChannels as u8 => {
+                // The firmware leaves the radio on the last scanned channel
+                self.channel = index as u8;
+                self.firmware_scan = (value as u8..=index as u8)
+                    .filter(|&channel| self.device(channel, self.datarate).is_some())
+                    .collect();
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn device(&self, channel: u8, datarate: u16) -> Option<&[u8]> {
+        self.devices
+            .iter()
+            .find(|(c, d, _)| (*c, *d) == (channel, datarate))
+            .map(|(_, _, payload)| payload.as_slice())
+    }
+
+    // Send a packet on the current channel and return the ack status and payload
+    fn transmit(&mut self, data: &[u8]) -> (u8, Vec<u8>) {
+        self.packets.push(data.to_vec());
+
+        let noisy = self.noisy.contains(&self.channel);
+        match self.device(self.channel, self.datarate) {
+            Some(payload) if self.ack_enable => (0x03, payload.to_vec()),
+            // No ack after the 3 default retries
+            _ => (0x30 | if noisy { 0x02 } else { 0x00 }, vec![]),
+        }
+    }
+
+    fn bulk_out(&mut self, buf: &[u8]) {
+        if self.sniffer_mode {
+            // Broadcast [address, payload], not answered
+            self.packets.push(buf.to_vec());
+        } else if self.inline_mode != 0 {
+            // [size, datarate | ack enable, channel, address, payload]
+            self.datarate = (buf[1] & 0x0f) as u16;
+            self.ack_enable = buf[1] & 0x10 != 0;
+            self.channel = buf[2];
+            self.address.copy_from_slice(&buf[3..8]);
+            let (status, payload) = self.transmit(&buf[8..]);
+
+            // [size, status, rssi (OnWithRssi only), payload]
+            let mut answer = vec![0, status];
+            if self.inline_mode == 2 {
+                answer.push(40);
+            }
+            answer.extend_from_slice(&payload);
+            answer[0] = answer.len() as u8;
+            self.answers.push_back(answer);
+        } else {
+            let (status, payload) = self.transmit(buf);
+            // Packets sent without ack are not answered
+            if self.ack_enable {
+                self.answers
+                    .push_back([&[status], payload.as_slice()].concat());
+            }
+        }
+    }
+
+    fn event(&mut self, event: String) -> rusb::Result<()> {
+        self.events.push(event);
+        Ok(())
+    }
+}
+
+impl UsbDevice for FakeDongle {
+    fn write_control(
+        &self,
+        _request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buf: &[u8],
+        _timeout: Duration,
+    ) -> rusb::Result<usize> {
+        self.state().control(request, value, index, buf)?;
+        Ok(buf.len())
+    }
+
+    fn read_control(
+        &self,
+        _request_type: u8,
+        request: u8,
+        _value: u16,
+        _index: u16,
+        buf: &mut [u8],
+        _timeout: Duration,
+    ) -> rusb::Result<usize> {
+        let state = self.state();
+        let answer = match request {
+            r if r == UsbCommand::ScanChannels as u8 => state.firmware_scan.clone(),
+            r if r == UsbCommand::GetSnifferDropCount as u8 => vec![0; 4],
+            _ => return Err(rusb::Error::Pipe),
+        };
+        let length = answer.len().min(buf.len());
+        buf[..length].copy_from_slice(&answer[..length]);
+        Ok(length)
+    }
+
+    fn write_bulk(&self, _endpoint: u8, buf: &[u8], _timeout: Duration) -> rusb::Result<usize> {
+        let mut state = self.state();
+        if state.halted {
+            return Err(rusb::Error::Pipe);
+        }
+        state.bulk_out(buf);
+        Ok(buf.len())
+    }
+
+    fn read_bulk(&self, _endpoint: u8, buf: &mut [u8], _timeout: Duration) -> rusb::Result<usize> {
+        let mut state = self.state();
+        if state.halted {
+            return Err(rusb::Error::Pipe);
+        }
+        let answer = state.answers.pop_front().ok_or(rusb::Error::Timeout)?;
+        let length = answer.len().min(buf.len());
+        buf[..length].copy_from_slice(&answer[..length]);
+        Ok(length)
+    }
+
+    fn clear_halt(&self, endpoint: u8) -> rusb::Result<()> {
+        let mut state = self.state();
+        state.event(format!("clear_halt {:#04x}", endpoint))?;
+        if state.halted {
+            return Err(rusb::Error::Pipe);
+        }
+        Ok(())
+    }
+
+    fn active_configuration(&self) -> rusb::Result<u8> {
+        Ok(1)
+    }
+
+    fn set_active_configuration(&self, config: u8) -> rusb::Result<()> {
+        let mut state = self.state();
+        state.halted = false;
+        state.event(format!("set_configuration {}", config))
+    }
+
+    fn claim_interface(&self, interface: u8) -> rusb::Result<()> {
+        self.state().event(format!("claim {}", interface))
+    }
+
+    fn release_interface(&self, interface: u8) -> rusb::Result<()> {
+        self.state().event(format!("release {}", interface))
+    }
+
+    fn set_alternate_setting(&self, interface: u8, setting: u8) -> rusb::Result<()> {
+        self.state()
+            .event(format!("alternate_setting {} {}", interface, setting))
+    }
+
+    fn reset(&self) -> rusb::Result<()> {
+        self.state().event("reset".to_string())
+    }
+
+    fn serial(&self) -> Result<String> {
+        Ok("FAKE".to_string())
+    }
+
+    fn speed(&self) -> rusb::Speed {
+        rusb::Speed::Full
+    }
+
+    fn firmware_version(&self) -> Result<rusb::Version> {
+        Ok(rusb::Version::from_bcd(self.state().firmware_version))
+    }
+
+    fn max_packet_size(&self, _endpoint: u8) -> Result<u16> {
+        Ok(64)
+    }
+
+    fn interface_settings(&self) -> Result<Vec<(u8, u8)>> {
+        Ok(vec![(0, 0)])
+    }
+}
@@ -17,43 +17,75 @@
 //!  - **async** enables async versions of open/serial functions, the [SharedCrazyradio] async API, and async sniffer mode via [`Crazyradio::enter_sniffer_mode_async`]
 //!  - **serde** enables [serde](https://crates.io/crates/serde) serialization/deserialization of the [Channel] struct
 //!  - **packet_capture** enables functionality to capture packets by registering a callback which is called for each in/out packet
+//!  - **capture_json** enables [capture::JsonlSink] writing the captured packets as JSON lines
+//!  - **crtp** enables the [crtp] module with helpers to frame Crazyflie CRTP packets
+//!  - **testing** enables the [testing] module with a scriptable fake radio to test code without hardware
 
 #![deny(missing_docs)]
 
 #[cfg(feature = "shared_radio")]
 mod shared_radio;
 #[cfg(feature = "shared_radio")]
-pub use crate::shared_radio::{SharedCrazyradio, WeakSharedCrazyradio};
+pub use crate::shared_radio::{PendingPacket, SharedCrazyradio, WeakSharedCrazyradio};
 
 #[cfg(feature = "packet_capture")]
 pub mod capture;
 
+#[cfg(feature = "crtp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "crtp")))]
+pub mod crtp;
+
+pub mod transfer;
+
+#[cfg(feature = "testing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "testing")))]
+pub mod testing;
+
+mod usb;
+
+#[cfg(test)]
+mod fake_dongle;
+
 #[cfg(feature = "async")]
 mod async_sniffer;
 #[cfg(feature = "async")]
 pub use crate::async_sniffer::{ReceivedSnifferPacket, SnifferReceiver, SnifferSender};
 
+pub use rusb::Speed as UsbSpeed;
+
 use core::time::Duration;
-use std::sync::Arc;
 #[cfg(feature = "serde_support")]
 use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::usb::UsbDevice;
 
 type Result<T> = std::result::Result<T, Error>;
 
-fn find_crazyradio(
+// USB vendor and product ID of the Crazyradio
+fn is_crazyradio(vendor_id: u16, product_id: u16) -> bool {
+    vendor_id == 0x1915 && product_id == 0x7777
+}
+
+fn find_crazyradio<C: rusb::UsbContext>(
+    context: &C,
     nth: Option<usize>,
     serial: Option<&str>,
-) -> Result<rusb::Device<rusb::GlobalContext>> {
+) -> Result<rusb::Device<C>> {
     let mut n = 0;
 
-    for device in rusb::devices()?.iter() {
+    for device in context.devices()?.iter() {
         let device_desc = device.device_descriptor()?;
 
-        if device_desc.vendor_id() == 0x1915 && device_desc.product_id() == 0x7777 {
-            let handle = device.open()?;
+        if is_crazyradio(device_desc.vendor_id(), device_desc.product_id()) {
+            let handle = device.open().map_err(open_error)?;
 
-            if (nth == None || nth == Some(n))
-                && (serial == None || serial == Some(&get_serial(&device_desc, &handle)?))
+            if (nth.is_none() || nth == Some(n))
+                && (serial.is_none() || serial == Some(&get_serial(&device_desc, &handle)?))
             {
                 return Ok(device);
             }
@@ -63,6 +95,106 @@ fn find_crazyradio(
     Err(Error::NotFound)
 }
 
+// (interface number, alternate setting) pairs of the active configuration
+fn interface_settings<T: rusb::UsbContext>(device: &rusb::Device<T>) -> Result<Vec<(u8, u8)>> {
+    let config = device.active_config_descriptor()?;
+    Ok(config
+        .interfaces()
+        .flat_map(|interface| interface.descriptors())
+        .map(|descriptor| (descriptor.interface_number(), descriptor.setting_number()))
+        .collect())
+}
+
+// Addresses of the bulk endpoints of the active configuration
+fn bulk_endpoints<T: rusb::UsbContext>(device: &rusb::Device<T>) -> Result<Vec<u8>> {
+    let config = device.active_config_descriptor()?;
+    Ok(config
+        .interfaces()
+        .flat_map(|interface| interface.descriptors())
+        .flat_map(|descriptor| descriptor.endpoint_descriptors())
+        .filter(|endpoint| endpoint.transfer_type() == rusb::TransferType::Bulk)
+        .map(|endpoint| endpoint.address())
+        .collect())
+}
+
+// Check that `out` and `in_` are bulk endpoints of the right direction
+fn check_bulk_endpoints(endpoints: &[u8], out: u8, in_: u8) -> Result<()> {
+    let is_in = |address: u8| address & 0x80 != 0;
+    if is_in(out) || !is_in(in_) || !endpoints.contains(&out) || !endpoints.contains(&in_) {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(())
+}
+
+// With `strict` checking, a packet waiting for an ack can only be sent with
+// acks enabled and a packet not waiting for an ack with acks disabled
+fn check_ack_mode(strict: bool, ack_enable: bool, waits_for_ack: bool) -> Result<()> {
+    if strict && ack_enable != waits_for_ack {
+        Err(Error::InvalidArgument)
+    } else {
+        Ok(())
+    }
+}
+
+// Check that the simulated losses are valid percentages
+fn check_packet_loss(packet_loss_percent: u8, ack_loss_percent: u8) -> Result<()> {
+    if packet_loss_percent <= 100 && ack_loss_percent <= 100 {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgument)
+    }
+}
+
+// Send a null CRTP packet with `send_packet` and return whether it was acked
+pub(crate) fn ping_with(send_packet: impl FnOnce(&[u8], &mut [u8]) -> Result<Ack>) -> Result<bool> {
+    let mut ack_data = [0u8; 32];
+    Ok(send_packet(&[0xff], &mut ack_data)?.received)
+}
+
+// Validate the simulated losses, write them with `write` and record them in
+// `packet_loss` once written. The recorded losses are kept on error.
+pub(crate) fn set_packet_loss_with(
+    packet_loss: &mut (u8, u8),
+    packet_loss_percent: u8,
+    ack_loss_percent: u8,
+    write: impl FnOnce([u8; 2]) -> Result<()>,
+) -> Result<()> {
+    check_packet_loss(packet_loss_percent, ack_loss_percent)?;
+    write([packet_loss_percent, ack_loss_percent])?;
+    *packet_loss = (packet_loss_percent, ack_loss_percent);
+    Ok(())
+}
+
+// Check that `interface` has the `alternate_setting` in `settings`
+fn check_interface_setting(
+    settings: &[(u8, u8)],
+    interface: u8,
+    alternate_setting: u8,
+) -> Result<()> {
+    if settings.contains(&(interface, alternate_setting)) {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgument)
+    }
+}
+
+// Claiming the interface fails as busy when a kernel driver is bound to it
+fn claim_error(usb_error: rusb::Error, kernel_driver_active: bool) -> Error {
+    match usb_error {
+        rusb::Error::Busy if kernel_driver_active => Error::KernelDriverActive,
+        e => open_error(e),
+    }
+}
+
+// Errors when opening the radio are most often due to the system setup
+fn open_error(usb_error: rusb::Error) -> Error {
+    match usb_error {
+        rusb::Error::Access => Error::PermissionDenied,
+        rusb::Error::NotSupported => Error::DriverMissing,
+        e => Error::UsbError(e),
+    }
+}
+
 fn get_serial<T: rusb::UsbContext>(
     device_desc: &rusb::DeviceDescriptor,
     handle: &rusb::DeviceHandle<T>,
@@ -84,7 +216,7 @@ fn list_crazyradio_serials() -> Result<Vec<String>> {
     for device in rusb::devices()?.iter() {
         let device_desc = device.device_descriptor()?;
 
-        if device_desc.vendor_id() == 0x1915 && device_desc.product_id() == 0x7777 {
+        if is_crazyradio(device_desc.vendor_id(), device_desc.product_id()) {
             let handle: rusb::DeviceHandle<rusb::GlobalContext> = device.open()?;
 
             let languages = handle.read_languages(Duration::from_secs(1))?;
@@ -102,6 +234,51 @@ fn list_crazyradio_serials() -> Result<Vec<String>> {
     Ok(serials)
 }
 
+fn list_crazyradio_status(interface: u8) -> Result<Vec<(String, bool)>> {
+    let mut radios = vec![];
+
+    for device in rusb::devices()?.iter() {
+        let device_desc = device.device_descriptor()?;
+
+        if is_crazyradio(device_desc.vendor_id(), device_desc.product_id()) {
+            radios.push(radio_status(&device, &device_desc, interface));
+        }
+    }
+    Ok(radios)
+}
+
+// Serial number of a radio and if `interface` could be claimed. A radio that
+// cannot be opened, for example because it is used by another program on
+// some platforms, is not available and its serial number is unknown.
+fn radio_status<T: rusb::UsbContext>(
+    device: &rusb::Device<T>,
+    device_desc: &rusb::DeviceDescriptor,
+    interface: u8,
+) -> (String, bool) {
+    let Ok(handle) = device.open() else {
+        return (String::new(), false);
+    };
+    let serial = get_serial(device_desc, &handle).unwrap_or_default();
+
+    let available = handle.claim_interface(interface).is_ok();
+    if available {
+        // Closing the handle releases the interface anyway
+        let _ = handle.release_interface(interface);
+    }
+    (serial, available)
+}
+
+fn count_crazyradios() -> Result<usize> {
+    let mut count = 0;
+    for device in rusb::devices()?.iter() {
+        let device_desc = device.device_descriptor()?;
+        if is_crazyradio(device_desc.vendor_id(), device_desc.product_id()) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
 const USB_RX_DRAIN_MAX_PACKETS: usize = 64;
 
 fn drain_rx_queue_with<F>(mut read_bulk: F) -> Result<usize>
@@ -124,6 +301,467 @@ where
     Ok(drained)
 }
 
+// Probe each channel from `start` to `stop` included and return the ones for
+// which `probe` returns true. `cancel` is checked before each channel.
+// Returns Error::InvalidArgument if `start` is after `stop`.
+fn scan_channels_with<F>(
+    start: Channel,
+    stop: Channel,
+    cancel: &AtomicBool,
+    mut probe: F,
+) -> Result<Vec<Channel>>
+where
+    F: FnMut(Channel) -> Result<bool>,
+{
+    scan_hits_with(start, stop, cancel, |channel| {
+        Ok(probe(channel)?.then_some(channel))
+    })
+}
+
+// Same as scan_channels_with() but returns the hits reported by `probe`
+fn scan_hits_with<T, F>(
+    start: Channel,
+    stop: Channel,
+    cancel: &AtomicBool,
+    mut probe: F,
+) -> Result<Vec<T>>
+where
+    F: FnMut(Channel) -> Result<Option<T>>,
+{
+    if start > stop {
+        return Err(Error::InvalidArgument);
+    }
+
+    let mut result = vec![];
+    for channel in Channel::range(start, stop) {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(Error::Cancelled);
+        }
+
+        if let Some(hit) = probe(channel)? {
+            result.push(hit);
+        }
+    }
+    Ok(result)
+}
+
+// Split the channels from `start` to `stop` included in `parts` contiguous
+// ranges of about the same size. There are less ranges than `parts` if there
+// are less channels than parts.
+fn split_channels(start: Channel, stop: Channel, parts: usize) -> Vec<(Channel, Channel)> {
+    let count = (stop.0 as usize + 1).saturating_sub(start.0 as usize);
+    let parts = parts.min(count).max(1);
+
+    let mut ranges = vec![];
+    let mut first = start.0 as usize;
+    for part in 0..parts {
+        let size = count / parts + usize::from(part < count % parts);
+        if size > 0 {
+            ranges.push((Channel(first as u8), Channel((first + size - 1) as u8)));
+        }
+        first += size;
+    }
+    ranges
+}
+
+// Number of packets in a row that can be lost before
+// Crazyradio::send_packet_adaptive() steps down the datarate
+const ADAPTIVE_NO_ACK_THRESHOLD: usize = 3;
+
+// Next slower datarate, None if `datarate` is already the slowest
+fn slower_datarate(datarate: Datarate) -> Option<Datarate> {
+    match datarate {
+        Datarate::Dr2M => Some(Datarate::Dr1M),
+        Datarate::Dr1M => Some(Datarate::Dr250K),
+        Datarate::Dr250K => None,
+    }
+}
+
+// Time left from `now` until `deadline`, Error::Timeout if it has passed.
+// A zero timeout means no timeout for libusb, so it is an error as well.
+fn time_until(deadline: Instant, now: Instant) -> Result<Duration> {
+    match deadline.checked_duration_since(now) {
+        Some(left) if !left.is_zero() => Ok(left),
+        _ => Err(Error::Timeout),
+    }
+}
+
+// Weight of the newest ack in the retry count average of
+// Crazyradio::average_retries()
+const RETRY_AVERAGE_WEIGHT: f32 = 0.1;
+
+// Exponential moving average of the retry count updated with `ack`. The first
+// ack initializes the average.
+fn update_retry_average(average: Option<f32>, ack: &Ack) -> f32 {
+    let retry = ack.retry as f32;
+    match average {
+        Some(average) => average + RETRY_AVERAGE_WEIGHT * (retry - average),
+        None => retry,
+    }
+}
+
+// Callback called with the retry count of each ack, see
+// Crazyradio::set_retry_callback()
+type RetryCallback = Box<dyn FnMut(usize) + Send>;
+
+// Retry count monitoring of the acks received by a Crazyradio
+#[derive(Default)]
+struct RetryTracker {
+    // Moving average of the retry count, None before the first ack
+    average: Option<f32>,
+    // In a mutex to keep Crazyradio Sync, it is only accessed with &mut self
+    callback: Option<Mutex<RetryCallback>>,
+}
+
+impl RetryTracker {
+    fn record(&mut self, ack: &Ack) {
+        self.average = Some(update_retry_average(self.average, ack));
+        if let Some(callback) = &mut self.callback {
+            (callback.get_mut().unwrap())(ack.retry);
+        }
+    }
+}
+
+// True if `ack` has been received at least as strong as `min_rssi_dbm`.
+// Acks without RSSI measurement pass the filter.
+fn ack_meets_rssi(ack: &Ack, min_rssi_dbm: i8) -> bool {
+    ack.received && ack.rssi_dbm.is_none_or(|rssi| rssi >= min_rssi_dbm as i16)
+}
+
+// A channel probe is busy if the power detector tripped while no ack was
+// received: the ack itself trips the power detector.
+fn probe_is_busy(ack: &Ack) -> bool {
+    ack.power_detector && !ack.received
+}
+
+// Crazyflie link URI, for example radio://0/42/2M/E7E7E7E742
+fn link_uri(radio_index: usize, channel: Channel, datarate: Datarate, address: &[u8; 5]) -> String {
+    let datarate = match datarate {
+        Datarate::Dr250K => "250K",
+        Datarate::Dr1M => "1M",
+        Datarate::Dr2M => "2M",
+    };
+    let address: String = address.iter().map(|b| format!("{:02X}", b)).collect();
+    format!(
+        "radio://{}/{}/{}/{}",
+        radio_index,
+        u8::from(channel),
+        datarate,
+        address
+    )
+}
+
+// Apply a temporary setting with `set`, run `f` and then `restore` the setting,
+// even if `f` failed. The error of `f` takes precedence over the one of `restore`.
+fn with_temporary_setting<R, T>(
+    radio: &mut R,
+    set: impl FnOnce(&mut R) -> Result<()>,
+    f: impl FnOnce(&mut R) -> Result<T>,
+    restore: impl FnOnce(&mut R) -> Result<()>,
+) -> Result<T> {
+    set(radio)?;
+    let result = f(radio);
+    let restored = restore(radio);
+    let value = result?;
+    restored?;
+    Ok(value)
+}
+
+// Last `size` RSSI samples of Crazyradio::rssi_monitor()
+struct RssiWindow {
+    size: usize,
+    samples: std::collections::VecDeque<i16>,
+}
+
+impl RssiWindow {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            samples: std::collections::VecDeque::with_capacity(size),
+        }
+    }
+
+    // Add a sample, dropping the oldest one if the window is full, and
+    // return the statistics of the window
+    fn push(&mut self, rssi_dbm: i16) -> RssiStats {
+        if self.samples.len() == self.size {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(rssi_dbm);
+
+        let sum: i32 = self.samples.iter().map(|&rssi| rssi as i32).sum();
+        RssiStats {
+            min: *self.samples.iter().min().unwrap(),
+            avg: sum as f32 / self.samples.len() as f32,
+            max: *self.samples.iter().max().unwrap(),
+            samples: self.samples.len(),
+        }
+    }
+}
+
+// Step of Crazyradio::self_test(), run on the radio `R`
+type SelfTestFn<R> = fn(&mut R) -> Result<()>;
+
+// ARD register value for the smallest 250us step longer or equal to `delay`
+fn ard_time_register(delay: Duration) -> Result<u16> {
+    let steps = delay.as_micros().div_ceil(250).max(1);
+    if steps <= 16 {
+        Ok(steps as u16 - 1)
+    } else {
+        Err(Error::InvalidArgument)
+    }
+}
+
+// Time needed to receive an ack with a payload of `nbytes` bytes: the ack on-air
+// time (1 byte preamble, 5 bytes address, 9 bits packet control field, payload
+// and 2 bytes CRC) plus the 130us RX/TX turnaround of the receiver.
+fn ack_time(datarate: Datarate, nbytes: u8) -> Duration {
+    let bits = 8 * (1 + 5 + nbytes as u64 + 2) + 9;
+    let ns_per_bit = match datarate {
+        Datarate::Dr250K => 4000,
+        Datarate::Dr1M => 1000,
+        Datarate::Dr2M => 500,
+    };
+    Duration::from_nanos(bits * ns_per_bit) + Duration::from_micros(130)
+}
+
+// Time during which a packet is retried: `arc` retries each waiting `ard`.
+// A delay in bytes is the time needed to receive such an ack at `datarate`.
+// Delays are rounded up to the 250us steps of the radio.
+fn retransmit_window(arc: usize, ard: AutoRetryDelay, datarate: Datarate) -> Duration {
+    let delay = match ard {
+        AutoRetryDelay::Time(delay) => delay,
+        AutoRetryDelay::Bytes(nbytes) => ack_time(datarate, nbytes),
+    };
+    let steps = delay.as_micros().div_ceil(250).max(1) as u32;
+    Duration::from_micros(250) * steps * arc as u32
+}
+
+// Default size of the firmware-side scan result buffer, the firmware reports
+// at most 63 channels
+const DEFAULT_FIRMWARE_SCAN_BUFFER_SIZE: usize = 63;
+
+// Decode the firmware-side scan answer: one byte per acking channel. The
+// result is truncated if it fills the `buffer_size` bytes read while more
+// channels than that have been scanned.
+fn parse_firmware_scan(
+    answer: &[u8],
+    buffer_size: usize,
+    start: Channel,
+    stop: Channel,
+) -> Result<FirmwareScan> {
+    let channels = answer
+        .iter()
+        .map(|&channel| match Channel::from_number(channel) {
+            Ok(channel) if (start..=stop).contains(&channel) => Ok(channel),
+            _ => Err(Error::UsbProtocolError(format!(
+                "Firmware scan reported channel {} out of the scanned range",
+                channel
+            ))),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let scanned = (stop.0 - start.0) as usize + 1;
+    Ok(FirmwareScan {
+        channels,
+        truncated: answer.len() >= buffer_size && scanned > buffer_size,
+    })
+}
+
+// Largest payload of a radio packet
+const MAX_PAYLOAD_LENGTH: usize = 32;
+
+fn check_payload_length(data: &[u8]) -> Result<()> {
+    if data.len() > MAX_PAYLOAD_LENGTH {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(())
+}
+
+// Packet data longer than a radio payload is a programming error, it is
+// caught in debug builds without cost in release builds
+pub(crate) fn debug_assert_payload_length(data: &[u8]) {
+    debug_assert!(
+        data.len() <= MAX_PAYLOAD_LENGTH,
+        "packet data must be at most {} bytes, got {}",
+        MAX_PAYLOAD_LENGTH,
+        data.len()
+    );
+}
+
+// Packets sent waiting for an ack carry at least one byte: with inline mode
+// off, an empty packet would be an empty USB transfer that the radio does not
+// see as a packet, so the ack read would time out.
+fn check_packet_data(data: &[u8]) -> Result<()> {
+    if data.is_empty() {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(())
+}
+
+// Max packet size of the endpoint at `address` out of (address, wMaxPacketSize)
+// pairs. Only the 11 lower bits of wMaxPacketSize are the packet size.
+fn endpoint_max_packet_size(
+    mut endpoints: impl Iterator<Item = (u8, u16)>,
+    address: u8,
+) -> Option<u16> {
+    endpoints
+        .find(|&(endpoint, _)| endpoint == address)
+        .map(|(_, max_packet_size)| max_packet_size & 0x07ff)
+}
+
+// Max packet size of the endpoint at `address` of `device`
+fn device_max_packet_size<T: rusb::UsbContext>(
+    device: &rusb::Device<T>,
+    address: u8,
+) -> Result<u16> {
+    let config = device.active_config_descriptor()?;
+    let endpoints = config
+        .interfaces()
+        .flat_map(|interface| interface.descriptors())
+        .flat_map(|descriptor| descriptor.endpoint_descriptors())
+        .map(|endpoint| (endpoint.address(), endpoint.max_packet_size()));
+
+    endpoint_max_packet_size(endpoints, address)
+        .ok_or_else(|| Error::UsbProtocolError("Bulk IN endpoint descriptor not found".to_string()))
+}
+
+// Maximum size of the answer to a packet sent with inline mode off: [status, payload (32)]
+const MAX_ACK_SIZE: usize = 33;
+
+// Maximum size of the answer to a packet sent with inline mode on:
+// [size, status, rssi, payload (32)]
+const MAX_INLINE_ACK_SIZE: usize = 35;
+
+// Length of the receive buffer for a bulk IN endpoint of `max_packet_size`:
+// one USB packet, so that a read never overflows, and at least the longest
+// answer to a sent packet
+fn rx_buffer_length(max_packet_size: u16) -> usize {
+    usize::from(max_packet_size).max(MAX_INLINE_ACK_SIZE)
+}
+
+// Validate the size of an answer expected to be at most `max` bytes. Answers
+// are read as a full USB packet, larger than the longest ack: a larger size
+// means the device misbehaved, for example by sending a stale packet of
+// another mode, and the data cannot be trusted. It is reported as a protocol
+// error instead of being decoded.
+fn check_read_size(received: usize, max: usize) -> Result<usize> {
+    if received > max {
+        return Err(Error::UsbProtocolError(format!(
+            "Radio returned {} bytes, expected at most {}",
+            received, max
+        )));
+    }
+    Ok(received)
+}
+
+// Decode the answer to a packet sent with inline mode off: [status, payload...]
+// Returns the ack and the range of the payload in `answer`.
+fn decode_ack(answer: &[u8]) -> Result<(Ack, Range<usize>)> {
+    let Some(&status) = answer.first() else {
+        return Err(Error::UsbProtocolError("Empty ack from radio".to_string()));
+    };
+
+    let ack = AckStatus::from_bits(status).to_ack(answer.len() - 1, None);
+
+    Ok((ack, 1..answer.len()))
+}
+
+// Copy as much of the ack `payload` as fits in `ack_data` and return the
+// number of bytes copied. Bytes of `ack_data` past the payload are untouched.
+fn copy_ack_payload(payload: &[u8], ack_data: &mut [u8]) -> usize {
+    let copy_len = payload.len().min(ack_data.len());
+    ack_data[..copy_len].copy_from_slice(&payload[..copy_len]);
+    copy_len
+}
+
+// A setting is sent to the radio by control transfer only with inline mode
+// off, where it is not sent with each packet, and, when the settings are
+// cached, only if it differs from the `current` one
+fn setting_needs_write<T: PartialEq>(
+    inline_mode: InlineMode,
+    cache_settings: bool,
+    current: &T,
+    requested: &T,
+) -> bool {
+    inline_mode.is_off() && (!cache_settings || current != requested)
+}
+
+// Copy the undecoded `answer` to `out`, truncating it if `out` is too short.
+// Returns the length of the answer.
+fn copy_raw_answer(answer: &[u8], out: &mut [u8]) -> usize {
+    copy_ack_payload(answer, out);
+    answer.len()
+}
+
+// The RSSI of the acks is reported by the firmware 5.3 and newer, older
+// firmware do not send the RSSI byte in the inline header
+fn firmware_supports_rssi(version: rusb::Version) -> bool {
+    version >= rusb::Version::from_bcd(0x0530)
+}
+
+// The firmware-side channel scan is implemented since the Crazyradio PA
+// firmware 0.5, as checked by the Python driver
+fn firmware_supports_scan(version: rusb::Version) -> bool {
+    version >= rusb::Version::from_bcd(0x0050)
+}
+
+// Decode the answer to a packet sent with inline mode on:
+// [size, status, rssi (OnWithRssi only), payload...]
+// Returns the ack and the range of the payload in `answer`.
+fn decode_inline_ack(answer: &[u8], inline_mode: InlineMode) -> Result<(Ack, Range<usize>)> {
+    const IN_HEADER_LENGTH: usize = 2;
+    const IN_HEADER_RSSI_LENGTH: usize = 3;
+
+    const _IN_HEADER_INVALID_SETTING: u8 = 0x04;
+
+    const IN_HEADER_RSSI: usize = 2;
+
+    let header_length = match inline_mode {
+        InlineMode::On => IN_HEADER_LENGTH,
+        InlineMode::OnWithRssi => IN_HEADER_RSSI_LENGTH,
+        InlineMode::Off => unreachable!(),
+    };
+    // The first byte of the answer is the size of the answer
+    // The minimum possible answer is 2 bytes [size, header]
+    if (answer.len() < header_length) || ((answer[0] as usize) != answer.len()) {
+        return Err(Error::UsbProtocolError(
+            "Inline header from radio malformed, try to update your radio".to_string(),
+        ));
+    }
+
+    let status = AckStatus::from_bits(answer[1]);
+
+    // Decode RSSI value if available
+    let rssi_dbm = if inline_mode == InlineMode::OnWithRssi && status.received() {
+        Some(-(answer[IN_HEADER_RSSI] as i16))
+    } else {
+        None
+    };
+
+    let ack = status.to_ack(answer.len() - header_length, rssi_dbm);
+
+    Ok((ack, header_length..answer.len()))
+}
+
+// Decode the answer to a sent packet received with `inline_mode`. Returns the
+// ack and the range of the ack payload in `answer`.
+fn decode_answer(answer: &[u8], inline_mode: InlineMode) -> Result<(Ack, Range<usize>)> {
+    if inline_mode.is_on() {
+        decode_inline_ack(answer, inline_mode)
+    } else {
+        decode_ack(answer)
+    }
+}
+
+// USB request types of the vendor control requests
+const REQUEST_TYPE_VENDOR_OUT: u8 = 0x40;
+const REQUEST_TYPE_VENDOR_IN: u8 = 0xC0;
+
+// Bulk endpoints used to send and receive packets
+const ENDPOINT_BULK_OUT: u8 = 0x01;
+const ENDPOINT_BULK_IN: u8 = 0x81;
+
 enum UsbCommand {
     SetRadioChannel = 0x01,
     SetRadioAddress = 0x02,
@@ -133,7 +771,7 @@ enum UsbCommand {
     SetRadioArc = 0x06,
     AckEnable = 0x10,
     SetContCarrier = 0x20,
-    // ScanChannels = 0x21,
+    ScanChannels = 0x21,
     SetInlineMode = 0x23,
     SetRadioMode = 0x24,
     SetSnifferAddress = 0x25,
@@ -166,11 +804,154 @@ impl InlineMode {
     }
 }
 
+/// Options and flags used to open a Crazyradio
+///
+/// This allows more control over how the radio is opened than the
+/// `Crazyradio::open_*` functions. By default the first radio found is opened
+/// and reset to boot values.
+///
+/// Example:
+/// ```no_run
+/// use crazyradio::OpenOptions;
+/// # fn main() -> Result<(), crazyradio::Error> {
+/// let mut cr = OpenOptions::new()
+///     .serial("FD61E54B7A")
+///     .reset_on_open(false)
+///     .open()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct OpenOptions {
+    nth: Option<usize>,
+    serial: Option<String>,
+    reset_on_open: bool,
+    interface: u8,
+    alternate_setting: Option<u8>,
+    endpoint_out: u8,
+    endpoint_in: u8,
+    detach_kernel_driver: bool,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenOptions {
+    /// Create the default options: open the first radio and reset it
+    pub fn new() -> Self {
+        OpenOptions {
+            nth: None,
+            serial: None,
+            reset_on_open: true,
+            interface: 0,
+            alternate_setting: None,
+            endpoint_out: ENDPOINT_BULK_OUT,
+            endpoint_in: ENDPOINT_BULK_IN,
+            detach_kernel_driver: true,
+        }
+    }
+
+    /// Open the nth Crazyradio detected, see [Crazyradio::open_nth()]
+    pub fn nth(&mut self, nth: usize) -> &mut Self {
+        self.nth = Some(nth);
+        self
+    }
+
+    /// Open the Crazyradio with this serial number, see [Crazyradio::open_by_serial()]
+    pub fn serial(&mut self, serial: &str) -> &mut Self {
+        self.serial = Some(serial.to_owned());
+        self
+    }
+
+    /// Reset the radio to boot values when opening it (default: true)
+    ///
+    /// When disabled, the radio is opened without sending any configuration
+    /// to it, which keeps the current configuration of the dongle untouched.
+    /// The radio is then in an unknown state: the configuration tracked by
+    /// the [Crazyradio] object and its inline mode do not reflect the dongle,
+    /// so cached setters might skip needed transfers and packets might be
+    /// sent with the wrong protocol. Call [Crazyradio::reset()] before
+    /// communicating with the radio.
+    pub fn reset_on_open(&mut self, reset_on_open: bool) -> &mut Self {
+        self.reset_on_open = reset_on_open;
+        self
+    }
+
+    /// USB interface to claim (default: 0)
+    ///
+    /// The radio protocol is implemented by interface 0, other interfaces
+    /// exposed by some firmware are only useful with the raw transfer
+    /// functions like [Crazyradio::raw_bulk_write()].
+    pub fn interface(&mut self, interface: u8) -> &mut Self {
+        self.interface = interface;
+        self
+    }
+
+    /// Alternate setting to select on the claimed interface (default: the
+    /// setting selected by the device), see [Crazyradio::set_alternate_setting()]
+    pub fn alternate_setting(&mut self, alternate_setting: u8) -> &mut Self {
+        self.alternate_setting = Some(alternate_setting);
+        self
+    }
+
+    /// Bulk endpoints used to send and receive packets (default: 0x01 and 0x81)
+    ///
+    /// This allows to use firmware remapping the bulk endpoints. Opening the
+    /// radio fails with `Error::InvalidArgument` if the device does not have
+    /// a bulk OUT endpoint `out` and a bulk IN endpoint `in_`.
+    pub fn bulk_endpoints(&mut self, out: u8, in_: u8) -> &mut Self {
+        self.endpoint_out = out;
+        self.endpoint_in = in_;
+        self
+    }
+
+    /// Detach a kernel driver bound to the claimed interface (default: true)
+    ///
+    /// On Linux, a kernel driver like `cdc_acm` can be bound to the interface
+    /// and prevent claiming it. When enabled, libusb detaches the kernel
+    /// driver while the radio is open and attaches it back when the radio is
+    /// closed. This has no effect on platforms without kernel driver support.
+    ///
+    /// When disabled, opening a radio whose interface is used by a kernel
+    /// driver fails with `Error::KernelDriverActive`.
+    pub fn detach_kernel_driver(&mut self, detach_kernel_driver: bool) -> &mut Self {
+        self.detach_kernel_driver = detach_kernel_driver;
+        self
+    }
+
+    /// Return the serial numbers of connected Crazyradios and if the
+    /// interface of these options is available, see [Crazyradio::list_with_status()]
+    pub fn list_with_status(&self) -> Result<Vec<(String, bool)>> {
+        list_crazyradio_status(self.interface)
+    }
+
+    /// Open a Crazyradio with these options
+    pub fn open(&self) -> Result<Crazyradio> {
+        self.open_with_context(&rusb::GlobalContext::default())
+    }
+
+    /// Open a Crazyradio with these options in a libusb `context`, see
+    /// [Crazyradio::open_with_context()]
+    pub fn open_with_context<C: rusb::UsbContext + 'static>(
+        &self,
+        context: &C,
+    ) -> Result<Crazyradio<C>> {
+        Crazyradio::open_with_context(context, self)
+    }
+}
+
 /// Represents a Crazyradio
 ///
 /// Holds the USB connection to a Crazyradio dongle.
 /// The connection is closed when this object goes out of scope.Crazyradio
 ///
+/// The radio is opened in the libusb global context by default. Applications
+/// managing their own libusb context can open the radio in it with
+/// [Crazyradio::open_with_context()].
+///
 /// Usage example:
 /// ```no_run
 /// use crazyradio::{Crazyradio, Error, Channel};
@@ -192,11 +973,20 @@ impl InlineMode {
 ///     Ok(())
 /// }
 /// ```
-pub struct Crazyradio {
-    device_desciptor: rusb::DeviceDescriptor,
-    device_handle: Arc<rusb::DeviceHandle<rusb::GlobalContext>>,
+pub struct Crazyradio<C: rusb::UsbContext = rusb::GlobalContext> {
+    firmware_version: rusb::Version,
+    device_handle: Arc<dyn UsbDevice>,
+    // The device handle is opened in a libusb context of type C
+    context: PhantomData<C>,
+    // Claimed USB interface and its bulk endpoints
+    interface: u8,
+    endpoint_out: u8,
+    endpoint_in: u8,
 
     cache_settings: bool,
+    strict_ack_check: bool,
+    scan_settle_time: Duration,
+    firmware_scan_buffer_size: usize,
     inline_mode: InlineMode,
     saved_inline_mode: InlineMode,
     sniffer_mode: bool,
@@ -207,6 +997,25 @@ pub struct Crazyradio {
     datarate: Datarate,
     ack_enable: bool,
 
+    // Tracked state of the settings that are always sent to the radio
+    power: Power,
+    arc: usize,
+    ard: AutoRetryDelay,
+    cont_carrier: bool,
+    packet_loss: (u8, u8),
+
+    // Packets lost in a row by send_packet_adaptive()
+    adaptive_no_acks: usize,
+    // Spacing of the packets sent by send_packet_throttled()
+    rate_limiter: RateLimiter,
+
+    stats: UsbStats,
+    retries: RetryTracker,
+
+    // Receive buffer for the answers to sent packets, sized from the max
+    // packet size of the bulk IN endpoint
+    rx_buffer: Vec<u8>,
+
     /// Radio serial number (for capture identification)
     #[cfg(feature = "packet_capture")]
     serial: String,
@@ -227,7 +1036,7 @@ impl Crazyradio {
     ///
     /// The dongle is reset to boot values before being returned
     pub fn open_nth(nth: usize) -> Result<Self> {
-        Self::open_generic(Some(nth), None)
+        OpenOptions::new().nth(nth).open()
     }
 
     /// Open a Crazyradio by specifying its serial number
@@ -241,52 +1050,35 @@ impl Crazyradio {
     /// # }
     /// ```
     pub fn open_by_serial(serial: &str) -> Result<Self> {
-        Self::open_generic(None, Some(serial))
+        OpenOptions::new().serial(serial).open()
     }
 
-    // Generic version of the open function, called by the other open_* functions
-    fn open_generic(nth: Option<usize>, serial: Option<&str>) -> Result<Self> {
-        let device = find_crazyradio(nth, serial)?;
-
-        let device_desciptor = device.device_descriptor()?;
-        let device_handle = Arc::new(device.open()?);
-
-        device_handle.claim_interface(0)?;
-
-        // Make sure the dongle version is >= 0.5
-        let version = device_desciptor.device_version();
-        let version = version.major() as f64
-            + (version.minor() as f64 / 10.0)
-            + (version.sub_minor() as f64 / 100.0);
-        if version < 0.5 {
-            return Err(Error::DongleVersionNotSupported);
-        }
-
-        #[cfg(feature = "packet_capture")]
-        let serial = get_serial(&device_desciptor, &device_handle).unwrap_or_default();
-
-        let mut cr = Crazyradio {
-            device_desciptor,
-            device_handle,
-
-            cache_settings: true,
-            inline_mode: InlineMode::Off,
-            saved_inline_mode: InlineMode::Off,
-            sniffer_mode: false,
-
-            channel: Channel::from_number(2).unwrap(),
-            address: [0xe7; 5],
-            datarate: Datarate::Dr2M,
-
-            ack_enable: true,
-
-            #[cfg(feature = "packet_capture")]
-            serial,
+    /// Iterate over the connected Crazyradios, opening them in turn
+    ///
+    /// Each radio is opened when the iterator reaches it, in the order of
+    /// [Crazyradio::open_nth()]. If a radio cannot be opened, for example
+    /// because it is used by another program, its error is yielded and the
+    /// iteration continues with the next one.
+    ///
+    /// Example:
+    /// ```no_run
+    /// for cr in crazyradio::Crazyradio::enumerate() {
+    ///     match cr {
+    ///         Ok(cr) => println!("Opened {:?}", cr.serial()),
+    ///         Err(e) => println!("Cannot open radio: {}", e),
+    ///     }
+    /// }
+    /// ```
+    pub fn enumerate() -> impl Iterator<Item = Result<Self>> {
+        // An error counting the radios is yielded alone
+        let (count, error) = match count_crazyradios() {
+            Ok(count) => (count, None),
+            Err(e) => (0, Some(e)),
         };
-
-        cr.reset()?;
-
-        Ok(cr)
+        error
+            .map(Err)
+            .into_iter()
+            .chain((0..count).map(Self::open_nth))
     }
 
     /// Return an ordered list of serial numbers of connected Crazyradios
@@ -296,9 +1088,334 @@ impl Crazyradio {
         list_crazyradio_serials()
     }
 
-    /// Return the serial number of this radio
+    /// Read the identity of a Crazyradio without claiming it
+    ///
+    /// The radio is selected with the `nth` and `serial` options of `options`,
+    /// the other options are ignored. Only the USB descriptors are read: the
+    /// USB interface is not claimed and the radio is not reset, so a radio
+    /// used by another program is not disturbed. This is intended for
+    /// monitoring and inventory tools.
+    ///
+    /// The device handle opened to read the descriptors is dropped before
+    /// returning.
+    pub fn inspect(options: &OpenOptions) -> Result<RadioInfo> {
+        let device = find_crazyradio(
+            &rusb::GlobalContext::default(),
+            options.nth,
+            options.serial.as_deref(),
+        )?;
+        let device_desc = device.device_descriptor()?;
+        let handle = device.open().map_err(open_error)?;
+
+        Ok(RadioInfo {
+            serial: get_serial(&device_desc, &handle)?,
+            firmware_version: device_desc.device_version(),
+        })
+    }
+
+    /// Open and configure a Crazyradio as given by environment variables
+    ///
+    /// This is intended for CI and automated test rigs. The following
+    /// variables are read, the unset ones keep their default:
+    ///  - `CRAZYRADIO_SERIAL`: serial number of the radio to open, see
+    ///    [Crazyradio::open_by_serial()]. The first radio is opened if unset.
+    ///  - `CRAZYRADIO_CHANNEL`: channel number, from 0 to 125
+    ///  - `CRAZYRADIO_DATARATE`: `250K`, `1M` or `2M`
+    ///  - `CRAZYRADIO_ADDRESS`: address as 10 hexadecimal digits, like
+    ///    `E7E7E7E7E7`
+    ///
+    /// Returns `Error::InvalidArgument` if a variable has an invalid value.
+    pub fn open_from_env() -> Result<Self> {
+        let (options, config) = env_config(|name| std::env::var(name).ok())?;
+        let mut cr = options.open()?;
+        cr.reset_to(&config)?;
+        Ok(cr)
+    }
+
+    /// Return the serial numbers of connected Crazyradios and if they are available
+    ///
+    /// The order of the list is the same as [Crazyradio::list_serials()]. A
+    /// radio is available if it could be opened and its USB interface 0
+    /// could be claimed, it is then released immediately. A radio used by
+    /// another program is not disturbed and is reported as not available. On
+    /// some platforms a radio in use cannot even be opened: its serial
+    /// number cannot be read and is reported empty. See
+    /// [OpenOptions::list_with_status()] to check another interface.
+    ///
+    /// This is best-effort: another program can claim or release a radio
+    /// right after it has been checked, so opening an available radio can
+    /// still fail.
+    pub fn list_with_status() -> Result<Vec<(String, bool)>> {
+        OpenOptions::new().list_with_status()
+    }
+
+    /// Estimate the longest duration of a scan
+    ///
+    /// Returns the time needed to send `repeats` packets on each channel from
+    /// `start` to `stop` included, for `datarates` datarates, when sending one
+    /// packet takes `per_packet`. A channel is skipped as soon as one packet is
+    /// acked, so a scan finding channels is shorter. `per_packet` can be
+    /// measured with [Crazyradio::calibrate_latency()] on a channel where no
+    /// device answers. The estimation saturates at `Duration::MAX`.
+    pub fn estimated_scan_duration(
+        start: Channel,
+        stop: Channel,
+        repeats: usize,
+        datarates: usize,
+        per_packet: Duration,
+    ) -> Duration {
+        let channels = Channel::range(start, stop).count();
+        let packets = channels.saturating_mul(repeats).saturating_mul(datarates);
+        u32::try_from(packets)
+            .ok()
+            .and_then(|packets| per_packet.checked_mul(packets))
+            .unwrap_or(Duration::MAX)
+    }
+}
+
+impl<C: rusb::UsbContext> Crazyradio<C> {
+    /// Open a Crazyradio selected by `options` in a libusb `context`
+    ///
+    /// This allows applications managing their own `rusb::Context`, for
+    /// example to run the libusb event loop or to isolate several sets of
+    /// devices, to use the Crazyradio in it. All the open_* functions use the
+    /// libusb global context.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use crazyradio::{Crazyradio, OpenOptions};
+    /// # fn main() -> Result<(), crazyradio::Error> {
+    /// let context = rusb::Context::new()?;
+    /// let mut cr = Crazyradio::open_with_context(&context, OpenOptions::new().nth(0))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn open_with_context(context: &C, options: &OpenOptions) -> Result<Self>
+    where
+        C: 'static,
+    {
+        let device = find_crazyradio(context, options.nth, options.serial.as_deref())?;
+        Self::open_device(device, options)
+    }
+
+    /// Open a Crazyradio from a USB device enumerated by the application
+    ///
+    /// This allows applications enumerating the USB devices themselves, for
+    /// example to share the enumeration with other peripherals, to use a
+    /// Crazyradio they found. The radio is opened and reset as with
+    /// [Crazyradio::open_first()].
+    ///
+    /// Returns `Error::InvalidArgument` if `device` is not a Crazyradio.
+    ///
+    /// Example:
+    /// ```no_run
+    /// use crazyradio::Crazyradio;
+    /// # fn main() -> Result<(), crazyradio::Error> {
+    /// for device in rusb::devices()?.iter() {
+    ///     if let Ok(cr) = Crazyradio::from_device(device) {
+    ///         println!("Opened {}", cr.serial()?);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_device(device: rusb::Device<C>) -> Result<Self>
+    where
+        C: 'static,
+    {
+        let device_desc = device.device_descriptor()?;
+        if !is_crazyradio(device_desc.vendor_id(), device_desc.product_id()) {
+            return Err(Error::InvalidArgument);
+        }
+        Self::open_device(device, &OpenOptions::new())
+    }
+
+    // Open `device` with `options`, the device selection options are ignored
+    fn open_device(device: rusb::Device<C>, options: &OpenOptions) -> Result<Self>
+    where
+        C: 'static,
+    {
+        let device_handle = device.open().map_err(open_error)?;
+
+        check_bulk_endpoints(
+            &bulk_endpoints(&device)?,
+            options.endpoint_out,
+            options.endpoint_in,
+        )?;
+        let settings = interface_settings(&device)?;
+        check_interface_setting(
+            &settings,
+            options.interface,
+            options.alternate_setting.unwrap_or(0),
+        )?;
+        if options.detach_kernel_driver {
+            // Fails on platforms without kernel driver support, there is then
+            // no driver to detach
+            let _ = device_handle.set_auto_detach_kernel_driver(true);
+        }
+        device_handle
+            .claim_interface(options.interface)
+            .map_err(|e| {
+                let kernel_driver_active = device_handle
+                    .kernel_driver_active(options.interface)
+                    .unwrap_or(false);
+                claim_error(e, kernel_driver_active)
+            })?;
+        if let Some(alternate_setting) = options.alternate_setting {
+            device_handle.set_alternate_setting(options.interface, alternate_setting)?;
+        }
+
+        Self::from_usb(Arc::new(device_handle), options)
+    }
+
+    // Create the radio driving the claimed interface of `device_handle`
+    fn from_usb(device_handle: Arc<dyn UsbDevice>, options: &OpenOptions) -> Result<Self> {
+        // Make sure the dongle version is >= 0.5
+        let firmware_version = device_handle.firmware_version()?;
+        let version = firmware_version.major() as f64
+            + (firmware_version.minor() as f64 / 10.0)
+            + (firmware_version.sub_minor() as f64 / 100.0);
+        if version < 0.5 {
+            return Err(Error::DongleVersionNotSupported);
+        }
+
+        let rx_buffer_length =
+            rx_buffer_length(device_handle.max_packet_size(options.endpoint_in)?);
+
+        #[cfg(feature = "packet_capture")]
+        let serial = device_handle.serial().unwrap_or_default();
+
+        let mut cr = Crazyradio {
+            firmware_version,
+            device_handle,
+            context: PhantomData,
+            interface: options.interface,
+            endpoint_out: options.endpoint_out,
+            endpoint_in: options.endpoint_in,
+
+            cache_settings: true,
+            strict_ack_check: false,
+            scan_settle_time: Duration::ZERO,
+            firmware_scan_buffer_size: DEFAULT_FIRMWARE_SCAN_BUFFER_SIZE,
+            inline_mode: InlineMode::Off,
+            saved_inline_mode: InlineMode::Off,
+            sniffer_mode: false,
+
+            channel: Channel::from_number(2).unwrap(),
+            address: [0xe7; 5],
+            datarate: Datarate::Dr2M,
+
+            ack_enable: true,
+
+            power: Power::P0dBm,
+            arc: 3,
+            ard: AutoRetryDelay::Bytes(32),
+            cont_carrier: false,
+            packet_loss: (0, 0),
+            adaptive_no_acks: 0,
+            rate_limiter: RateLimiter::new(),
+
+            stats: UsbStats::default(),
+            retries: RetryTracker::default(),
+
+            rx_buffer: vec![0; rx_buffer_length],
+
+            #[cfg(feature = "packet_capture")]
+            serial,
+        };
+
+        if options.reset_on_open {
+            cr.reset()?;
+        }
+        Ok(cr)
+    }
+
+    /// Return the serial number of this radio
     pub fn serial(&self) -> Result<String> {
-        get_serial(&self.device_desciptor, &self.device_handle)
+        self.device_handle.serial()
+    }
+
+    /// Return the speed at which the dongle is connected to the USB host
+    ///
+    /// The Crazyradio is a full speed device, a lower speed or a hub between
+    /// the radio and the host can explain a lower than expected throughput.
+    pub fn usb_speed(&self) -> UsbSpeed {
+        self.device_handle.speed()
+    }
+
+    /// Return the maximum packet size of the bulk IN endpoint
+    ///
+    /// This is the largest answer the radio can send in one USB packet, 64
+    /// bytes for the Crazyradio. The buffer receiving the answers to the sent
+    /// packets is sized from it when the radio is opened.
+    pub fn max_packet_size(&self) -> Result<u16> {
+        self.device_handle.max_packet_size(self.endpoint_in)
+    }
+
+    /// Select an alternate setting of the claimed USB interface
+    ///
+    /// Returns `Error::InvalidArgument` if the interface has no such
+    /// alternate setting.
+    pub fn set_alternate_setting(&mut self, alternate_setting: u8) -> Result<()> {
+        let settings = self.device_handle.interface_settings()?;
+        check_interface_setting(&settings, self.interface, alternate_setting)?;
+        self.device_handle
+            .set_alternate_setting(self.interface, alternate_setting)?;
+        Ok(())
+    }
+
+    /// Recover the bulk endpoints after a transfer error left them stalled
+    ///
+    /// Clears the halt condition of the bulk OUT and IN endpoints. If clearing
+    /// the halt fails, the USB configuration of the dongle is selected again,
+    /// which resets the state of its endpoints, the radio interface is claimed
+    /// again and the halt cleared again. Returns the error if the endpoints
+    /// could not be recovered.
+    ///
+    /// This is the lightest recovery, to try first when the packet transfers
+    /// fail with `rusb::Error::Pipe` or keep timing out while the control
+    /// requests still work. The dongle and its radio configuration are kept.
+    /// If it fails, [Crazyradio::usb_reset()] resets the whole USB port of the
+    /// dongle, which is slower and may restart its firmware.
+    ///
+    /// Answers of packets sent before the recovery may still be queued, see
+    /// [Crazyradio::set_channel_flushed()].
+    pub fn recover_endpoints(&mut self) -> Result<()> {
+        let handle = &self.device_handle;
+        let endpoints = [self.endpoint_out, self.endpoint_in];
+        if endpoints.iter().all(|&e| handle.clear_halt(e).is_ok()) {
+            return Ok(());
+        }
+
+        let configuration = handle.active_configuration()?;
+        handle.release_interface(self.interface)?;
+        handle.set_active_configuration(configuration)?;
+        handle.claim_interface(self.interface)?;
+        for endpoint in endpoints {
+            handle.clear_halt(endpoint)?;
+        }
+        Ok(())
+    }
+
+    /// Reset the USB port of the dongle to recover it from a wedged state.
+    ///
+    /// This performs a USB port reset and claims the radio interface again.
+    /// The dongle firmware may have been restarted by the reset, call
+    /// [Crazyradio::reset()] or [Crazyradio::reset_to()] to bring the radio
+    /// back to a known configuration afterward.
+    ///
+    /// If the dongle re-enumerates on the bus, this handle cannot be used
+    /// anymore and `Error::NotFound` is returned: the radio has to be opened
+    /// again.
+    pub fn usb_reset(&mut self) -> Result<()> {
+        match self.device_handle.reset() {
+            Ok(()) => {}
+            Err(rusb::Error::NotFound) => return Err(Error::NotFound),
+            Err(e) => return Err(e.into()),
+        }
+        self.device_handle.claim_interface(self.interface)?;
+
+        Ok(())
     }
 
     /// Reset dongle parameters to boot values.
@@ -315,31 +1432,23 @@ impl Crazyradio {
         // Always exit sniffer mode unconditionally: a previous session may
         // have left the radio in sniffer mode. Ignore errors since older
         // firmware without sniffer support will reject the command.
-        let _ = self.device_handle.write_control(
-            0x40,
-            UsbCommand::SetRadioMode as u8,
-            0,
-            0,
-            &[],
-            Duration::from_secs(1),
-        );
+        let _ = self.control_out(UsbCommand::SetRadioMode, 0, 0, &[]);
         self.sniffer_mode = false;
 
         // Try to set inline mode, ignore failure as this is not fatal (old radio FW do not implement it and will just be slower)
         // We set it on first and then with rssi, this way the dongle is set to the maximum inline mode supported
         _ = self.set_inline_mode(InlineMode::On);
-        _ = self.set_inline_mode(InlineMode::OnWithRssi);
-
-        self.set_datarate(Datarate::Dr2M)?;
-        self.set_channel(Channel::from_number(2).unwrap())?;
-        self.set_cont_carrier(false)?;
-        self.set_address(&[0xe7, 0xe7, 0xe7, 0xe7, 0xe7])?;
-        self.set_power(Power::P0dBm)?;
-        self.set_arc(3)?;
-        self.set_ard_bytes(32)?;
-        self.set_ack_enable(true)?;
+        if self.supports_rssi_firmware() {
+            _ = self.set_inline_mode(InlineMode::OnWithRssi);
+        }
+
+        let boot_config = RadioConfig::default();
+        for change in config_changes(None, &boot_config) {
+            self.apply_config_change(change)?;
+        }
 
         self.cache_settings = prev_cache_settings;
+        self.adaptive_no_acks = 0;
 
         // Drain again after the mode switch/reset sequence. Packets can still
         // arrive while the firmware exits sniffer mode, so the initial drain
@@ -349,10 +1458,206 @@ impl Crazyradio {
         Ok(())
     }
 
+    /// Reconfigure the radio to `config`, only sending the settings that differ
+    ///
+    /// The settings are compared to the state tracked by this object (see
+    /// [Crazyradio::config()]) and only the ones that changed are sent to the
+    /// dongle. Resetting to the current configuration does not generate any
+    /// USB transfer, which makes this function cheap to call when reconnecting.
+    ///
+    /// Unlike [Crazyradio::reset()], this does not drain the USB endpoint nor
+    /// change the inline or sniffer mode.
+    pub fn reset_to(&mut self, config: &RadioConfig) -> Result<()> {
+        self.apply_config_diff(config).map(|_| ())
+    }
+
+    /// Apply `config`, only sending the settings that differ from the tracked state
+    ///
+    /// Same as [Crazyradio::reset_to()] but returns the number of control
+    /// transfers issued, as counted by [Crazyradio::stats()]. This is
+    /// intended for applications switching between a few configurations, for
+    /// example to talk to several drones in turn: only the channel or address
+    /// is usually sent when switching. With inline mode on, the channel,
+    /// datarate and address are sent with each packet and changing them does
+    /// not issue any control transfer.
+    ///
+    /// If a setting fails to be sent, the error is returned and the following
+    /// settings are not sent.
+    pub fn apply_config_diff(&mut self, config: &RadioConfig) -> Result<usize> {
+        let before = self.stats.control_transfers;
+
+        for change in config_changes(Some(&self.config()), config) {
+            self.apply_config_change(change)?;
+        }
+
+        Ok((self.stats.control_transfers - before) as usize)
+    }
+
+    /// Return the radio configuration as tracked by this object
+    ///
+    /// The state is known since the last [Crazyradio::reset()] and is updated
+    /// by each setter.
+    pub fn config(&self) -> RadioConfig {
+        RadioConfig {
+            channel: self.channel,
+            datarate: self.datarate,
+            address: self.address,
+            power: self.power,
+            arc: self.arc,
+            ard: self.ard,
+            ack_enable: self.ack_enable,
+            cont_carrier: self.cont_carrier,
+        }
+    }
+
+    fn apply_config_change(&mut self, change: ConfigChange) -> Result<()> {
+        match change {
+            ConfigChange::Datarate(datarate) => self.set_datarate(datarate),
+            ConfigChange::Channel(channel) => self.set_channel(channel),
+            ConfigChange::ContCarrier(enable) => self.set_cont_carrier(enable),
+            ConfigChange::Address(address) => self.set_address(&address),
+            ConfigChange::Power(power) => self.set_power(power),
+            ConfigChange::Arc(arc) => self.set_arc(arc),
+            ConfigChange::Ard(AutoRetryDelay::Time(delay)) => self.set_ard_time(delay),
+            ConfigChange::Ard(AutoRetryDelay::Bytes(nbytes)) => self.set_ard_bytes(nbytes),
+            ConfigChange::AckEnable(ack_enable) => self.set_ack_enable(ack_enable),
+        }
+    }
+
+    /// Reset the RF parameters to boot values.
+    ///
+    /// Only the channel, datarate, address and power are reset, see
+    /// [RadioConfig::default()]. Unlike [Crazyradio::reset()], the
+    /// retransmission settings (ack enable, ARC and ARD), continious carrier
+    /// and inline mode are left untouched, so this can be used mid-session.
+    /// The settings are sent to the radio even if they are cached.
+    pub fn reset_rf(&mut self) -> Result<()> {
+        let prev_cache_settings = self.cache_settings;
+        self.cache_settings = false;
+
+        let result = rf_changes(&RadioConfig::default())
+            .into_iter()
+            .try_for_each(|change| self.apply_config_change(change));
+
+        self.cache_settings = prev_cache_settings;
+        result
+    }
+
+    /// Return the USB transfer statistics
+    ///
+    /// Every bulk and control transfer made by this object is counted since
+    /// it was opened or since the last call to [Crazyradio::reset_stats()].
+    /// The reads draining stale packets from the USB IN endpoint, which always
+    /// end with a timeout, are not counted. Transfers made by the sniffer
+    /// handles returned by the async sniffer mode are not counted either.
+    pub fn stats(&self) -> UsbStats {
+        self.stats
+    }
+
+    /// Reset the USB transfer statistics to zero
+    ///
+    /// This also resets the average returned by [Crazyradio::average_retries()].
+    pub fn reset_stats(&mut self) {
+        self.stats = UsbStats::default();
+        self.retries.average = None;
+    }
+
+    /// Return the moving average of the retry count of the acks
+    ///
+    /// This is an exponential moving average of [Ack::retry] over the packets
+    /// sent with an ack since the radio was opened or since the last call to
+    /// [Crazyradio::reset_stats()], each new ack weighting for 10%. It is a
+    /// smoothed link quality metric, for example to adapt the power or the
+    /// datarate. Returns 0 if no ack has been received yet.
+    pub fn average_retries(&self) -> f32 {
+        self.retries.average.unwrap_or(0.0)
+    }
+
+    /// Set a callback called with the retry count of each ack
+    ///
+    /// The callback is called after each packet sent with an ack, like with
+    /// [Crazyradio::send_packet()], with the [Ack::retry] count of its ack. This
+    /// allows monitoring the retransmissions of the link without inspecting
+    /// every ack. The callback is called from the thread sending the packet and
+    /// should return quickly. Setting a callback replaces the previous one.
+    pub fn set_retry_callback(&mut self, callback: impl FnMut(usize) + Send + 'static) {
+        self.retries.callback = Some(Mutex::new(Box::new(callback)));
+    }
+
+    /// Remove the callback set with [Crazyradio::set_retry_callback()]
+    pub fn clear_retry_callback(&mut self) {
+        self.retries.callback = None;
+    }
+
+    fn record_ack(&mut self, ack: &Ack) {
+        self.retries.record(ack);
+    }
+
+    fn control_out(
+        &mut self,
+        command: UsbCommand,
+        value: u16,
+        index: u16,
+        data: &[u8],
+    ) -> rusb::Result<usize> {
+        let result = self.device_handle.write_control(
+            REQUEST_TYPE_VENDOR_OUT,
+            command as u8,
+            value,
+            index,
+            data,
+            Duration::from_secs(1),
+        );
+        self.stats.record(UsbTransfer::Control, &result);
+        result
+    }
+
+    fn control_in(
+        &mut self,
+        command: UsbCommand,
+        value: u16,
+        index: u16,
+        buf: &mut [u8],
+    ) -> rusb::Result<usize> {
+        let result = self.device_handle.read_control(
+            REQUEST_TYPE_VENDOR_IN,
+            command as u8,
+            value,
+            index,
+            buf,
+            Duration::from_secs(1),
+        );
+        self.stats.record(UsbTransfer::Control, &result);
+        result
+    }
+
+    fn bulk_out(&mut self, data: &[u8], timeout: Duration) -> rusb::Result<usize> {
+        let result = self
+            .device_handle
+            .write_bulk(self.endpoint_out, data, timeout);
+        self.stats.record(UsbTransfer::BulkWrite, &result);
+        result
+    }
+
+    fn bulk_in(&mut self, buf: &mut [u8], timeout: Duration) -> rusb::Result<usize> {
+        let result = self.device_handle.read_bulk(self.endpoint_in, buf, timeout);
+        self.stats.record(UsbTransfer::BulkRead, &result);
+        result
+    }
+
+    // Same as bulk_in() reading one USB packet in rx_buffer
+    fn bulk_in_rx_buffer(&mut self, timeout: Duration) -> rusb::Result<usize> {
+        let result = self
+            .device_handle
+            .read_bulk(self.endpoint_in, &mut self.rx_buffer, timeout);
+        self.stats.record(UsbTransfer::BulkRead, &result);
+        result
+    }
+
     fn drain_rx_queue(&self) -> Result<usize> {
         drain_rx_queue_with(|buf| {
             self.device_handle
-                .read_bulk(0x81, buf, Duration::from_millis(1))
+                .read_bulk(self.endpoint_in, buf, Duration::from_millis(1))
         })
     }
 
@@ -368,17 +1673,52 @@ impl Crazyradio {
         self.cache_settings = cache_settings;
     }
 
+    /// Enable or disable checking that packets are sent with the matching ack setting
+    ///
+    /// If enabled, [Crazyradio::send_packet()] returns `Error::InvalidArgument`
+    /// when acks are disabled and [Crazyradio::send_packet_no_ack()] returns
+    /// `Error::InvalidArgument` when acks are enabled, without sending the
+    /// packet. This helps catching code toggling [Crazyradio::set_ack_enable()]
+    /// and losing track of the current setting.
+    ///
+    /// This is disabled by default: packets are sent whatever the ack setting.
+    pub fn set_strict_ack_check(&mut self, strict: bool) {
+        self.strict_ack_check = strict;
+    }
+
+    /// Set the time to wait after switching channel when scanning (default: 0)
+    ///
+    /// The scan functions, like [Crazyradio::scan_channels()], wait
+    /// `settle_time` after setting each channel and before sending packets on
+    /// it, to give time to the radio PLL to settle. This can avoid missing
+    /// acks when scanning fast, at the cost of making the scan longer by
+    /// `settle_time` per channel.
+    ///
+    /// With inline mode on, the channel is only sent to the radio together
+    /// with the packet: the firmware then handles the channel switch and the
+    /// settle time only delays the packets.
+    pub fn set_scan_settle_time(&mut self, settle_time: Duration) {
+        self.scan_settle_time = settle_time;
+    }
+
+    // Set the channel to scan and wait for the radio to settle
+    fn set_scan_channel(&mut self, channel: Channel) -> Result<()> {
+        self.set_channel(channel)?;
+        if !self.scan_settle_time.is_zero() {
+            std::thread::sleep(self.scan_settle_time);
+        }
+        Ok(())
+    }
+
     /// Set the radio channel.
     pub fn set_channel(&mut self, channel: Channel) -> Result<()> {
-        if self.inline_mode.is_off() && (!self.cache_settings || self.channel != channel) {
-            self.device_handle.write_control(
-                0x40,
-                UsbCommand::SetRadioChannel as u8,
-                channel.0 as u16,
-                0,
-                &[],
-                Duration::from_secs(1),
-            )?;
+        if setting_needs_write(
+            self.inline_mode,
+            self.cache_settings,
+            &self.channel,
+            &channel,
+        ) {
+            self.control_out(UsbCommand::SetRadioChannel, channel.0 as u16, 0, &[])?;
         }
 
         self.channel = channel;
@@ -386,17 +1726,43 @@ impl Crazyradio {
         Ok(())
     }
 
+    /// Discard pending answers from the radio and set the radio channel.
+    ///
+    /// An answer still queued in the USB IN endpoint belongs to a packet sent
+    /// with the previous configuration, it would be read as the ack of the
+    /// next packet sent on the new channel. This happens when a packet sent
+    /// with [Crazyradio::submit_packet()] has not been followed by
+    /// [Crazyradio::fetch_ack()] or after a read timed out. In normal
+    /// operation, where each packet is followed by reading its ack, plain
+    /// [Crazyradio::set_channel()] is enough and faster.
+    ///
+    /// Returns the number of discarded answers.
+    pub fn set_channel_flushed(&mut self, channel: Channel) -> Result<usize> {
+        let discarded = self.drain_rx_queue()?;
+        self.set_channel(channel)?;
+        Ok(discarded)
+    }
+
+    /// Discard pending answers from the radio and set the radio address.
+    ///
+    /// See [Crazyradio::set_channel_flushed()] for when this is needed.
+    ///
+    /// Returns the number of discarded answers.
+    pub fn set_address_flushed(&mut self, address: &[u8; 5]) -> Result<usize> {
+        let discarded = self.drain_rx_queue()?;
+        self.set_address(address)?;
+        Ok(discarded)
+    }
+
     /// Set the datarate.
     pub fn set_datarate(&mut self, datarate: Datarate) -> Result<()> {
-        if self.inline_mode.is_off() && (!self.cache_settings || self.datarate != datarate) {
-            self.device_handle.write_control(
-                0x40,
-                UsbCommand::SetDataRate as u8,
-                datarate as u16,
-                0,
-                &[],
-                Duration::from_secs(1),
-            )?;
+        if setting_needs_write(
+            self.inline_mode,
+            self.cache_settings,
+            &self.datarate,
+            &datarate,
+        ) {
+            self.control_out(UsbCommand::SetDataRate, datarate as u16, 0, &[])?;
         }
 
         self.datarate = datarate;
@@ -404,69 +1770,62 @@ impl Crazyradio {
         Ok(())
     }
 
+    /// Set the datarate and adapt the time to wait for the ack packet to it.
+    ///
+    /// The on-air time of a packet depends on the datarate, at 250K it is 8
+    /// times longer than at 2M so an ack delay tuned for 2M causes spurious
+    /// retries. This function sets the ack delay to the time needed to receive
+    /// an ack with a 32 bytes payload at `datarate`: the ack on-air time
+    /// (1 byte preamble, 5 bytes address, 9 bits packet control field, 32
+    /// bytes payload and 2 bytes CRC) plus the 130us the receiver needs to
+    /// switch between RX and TX, rounded up to the next 250us step.
+    ///
+    /// This gives 1.5ms at 250K, 500us at 1M and 500us at 2M.
+    pub fn set_datarate_with_auto_ard(&mut self, datarate: Datarate) -> Result<()> {
+        self.set_datarate(datarate)?;
+        self.set_ard_time(ack_time(datarate, 32))
+    }
+
     /// Set the radio address.
     pub fn set_address(&mut self, address: &[u8; 5]) -> Result<()> {
-        if self.inline_mode.is_off() && (!self.cache_settings || self.address != *address) {
-            self.device_handle.write_control(
-                0x40,
-                UsbCommand::SetRadioAddress as u8,
-                0,
-                0,
-                address,
-                Duration::from_secs(1),
-            )?;
+        if setting_needs_write(
+            self.inline_mode,
+            self.cache_settings,
+            &self.address,
+            address,
+        ) {
+            self.control_out(UsbCommand::SetRadioAddress, 0, 0, address)?;
         }
 
-        if self.cache_settings || self.inline_mode.is_on() {
-            self.address.copy_from_slice(address);
-        }
+        self.address.copy_from_slice(address);
 
         Ok(())
     }
 
     /// Set the transmit power.
     pub fn set_power(&mut self, power: Power) -> Result<()> {
-        self.device_handle.write_control(
-            0x40,
-            UsbCommand::SetRadioPower as u8,
-            power as u16,
-            0,
-            &[],
-            Duration::from_secs(1),
-        )?;
+        self.control_out(UsbCommand::SetRadioPower, power as u16, 0, &[])?;
+        self.power = power;
         Ok(())
     }
 
     /// Set time to wait for the ack packet.
+    ///
+    /// The radio supports delays from 250us to 4ms by step of 250us, `delay`
+    /// is rounded up to the next step. Returns `Error::InvalidArgument` if
+    /// `delay` is longer than 4ms.
     pub fn set_ard_time(&mut self, delay: Duration) -> Result<()> {
-        if delay <= Duration::from_millis(4000) {
-            // Set to step above or equal to `delay`
-            let ard = (delay.as_millis() as u16 / 250) - 1;
-            self.device_handle.write_control(
-                0x40,
-                UsbCommand::SetRadioArd as u8,
-                ard,
-                0,
-                &[],
-                Duration::from_secs(1),
-            )?;
-            Ok(())
-        } else {
-            Err(Error::InvalidArgument)
-        }
+        let ard = ard_time_register(delay)?;
+        self.control_out(UsbCommand::SetRadioArd, ard, 0, &[])?;
+        self.ard = AutoRetryDelay::Time(delay);
+        Ok(())
     }
 
     /// Set time to wait for the ack packet by specifying the max byte-length of the ack payload.
     pub fn set_ard_bytes(&mut self, nbytes: u8) -> Result<()> {
         if nbytes <= 32 {
-            self.device_handle.write_control(
-                0x40,
-                UsbCommand::SetRadioArd as u8,
-                0x80 | nbytes as u16,
-                0,
-                &[],
-                Duration::from_secs(1),
-            )?;
+            self.control_out(UsbCommand::SetRadioArd, 0x80 | nbytes as u16, 0, &[])?;
+            self.ard = AutoRetryDelay::Bytes(nbytes);
             Ok(())
         } else {
             Err(Error::InvalidArgument)
@@ -476,33 +1835,63 @@ impl Crazyradio {
     /// Set the number of time the radio will retry to send the packet if an ack packet is not received in time.
     pub fn set_arc(&mut self, arc: usize) -> Result<()> {
         if arc <= 15 {
-            self.device_handle.write_control(
-                0x40,
-                UsbCommand::SetRadioArc as u8,
-                arc as u16,
-                0,
-                &[],
-                Duration::from_secs(1),
-            )?;
+            self.control_out(UsbCommand::SetRadioArc, arc as u16, 0, &[])?;
+            self.arc = arc;
             Ok(())
         } else {
             Err(Error::InvalidArgument)
         }
     }
 
+    /// Set the number of retries and the time to wait for the ack together
+    ///
+    /// Same as calling [Crazyradio::set_arc()] and [Crazyradio::set_ard_time()]
+    /// or [Crazyradio::set_ard_bytes()], these two settings defining together
+    /// how long a packet is retried, see
+    /// [Crazyradio::total_retransmit_window()]. Returns
+    /// `Error::InvalidArgument` without changing any setting if one of them is
+    /// out of range.
+    pub fn set_retransmit(&mut self, count: usize, delay: AutoRetryDelay) -> Result<()> {
+        let valid_delay = match delay {
+            AutoRetryDelay::Time(delay) => ard_time_register(delay).is_ok(),
+            AutoRetryDelay::Bytes(nbytes) => nbytes <= 32,
+        };
+        if count > 15 || !valid_delay {
+            return Err(Error::InvalidArgument);
+        }
+
+        self.set_arc(count)?;
+        match delay {
+            AutoRetryDelay::Time(delay) => self.set_ard_time(delay),
+            AutoRetryDelay::Bytes(nbytes) => self.set_ard_bytes(nbytes),
+        }
+    }
+
+    /// Return the number of retries and the time to wait for the ack
+    ///
+    /// This is the state set by the last calls to [Crazyradio::set_arc()] and
+    /// [Crazyradio::set_ard_time()] or [Crazyradio::set_ard_bytes()], the
+    /// radio is not queried.
+    pub fn retransmit(&self) -> (usize, AutoRetryDelay) {
+        (self.arc, self.ard)
+    }
+
+    /// Return how long a packet that is never acked is retried
+    ///
+    /// This is the number of retries times the time waited for the ack, in
+    /// steps of 250us as configured in the radio. When the delay is set in
+    /// bytes, it is the time needed to receive an ack payload of this length
+    /// at the current datarate.
+    pub fn total_retransmit_window(&self) -> Duration {
+        retransmit_window(self.arc, self.ard, self.datarate)
+    }
+
     /// Set if the radio waits for an ack packet.
     ///
     /// Should be disabled when sending broadcast packets.
     pub fn set_ack_enable(&mut self, ack_enable: bool) -> Result<()> {
         if self.inline_mode.is_off() && ack_enable != self.ack_enable {
-            self.device_handle.write_control(
-                0x40,
-                UsbCommand::AckEnable as u8,
-                ack_enable as u16,
-                0,
-                &[],
-                Duration::from_secs(1),
-            )?;
+            self.control_out(UsbCommand::AckEnable, ack_enable as u16, 0, &[])?;
         }
 
         self.ack_enable = ack_enable;
@@ -510,40 +1899,603 @@ impl Crazyradio {
         Ok(())
     }
 
-    /// Sends a packet to a range of channel and returns a list of channel that acked
+    /// Return true if the radio waits for an ack packet
     ///
-    /// Used to activally scann for receives on channels. This function sends
-    pub fn scan_channels(
+    /// This is the state set by the last call to
+    /// [Crazyradio::set_ack_enable()], the radio is not queried.
+    pub fn ack_enabled(&self) -> bool {
+        self.ack_enable
+    }
+
+    /// Set the size of the result buffer of the firmware-side scan (default: 63)
+    ///
+    /// This is the maximum number of acking channels that
+    /// [Crazyradio::scan_channels_firmware()] can report. Returns
+    /// `Error::InvalidArgument` if `size` is 0.
+    pub fn set_firmware_scan_buffer_size(&mut self, size: usize) -> Result<()> {
+        if size == 0 {
+            return Err(Error::InvalidArgument);
+        }
+        self.firmware_scan_buffer_size = size;
+        Ok(())
+    }
+
+    /// Scan channels in the radio firmware and return the channels that acked
+    ///
+    /// The firmware sends `packet` on each channel from `start` to `stop`,
+    /// both included, and reports the channels that acked in one answer. This
+    /// is much faster than [Crazyradio::scan_channels()] which needs USB
+    /// transfers for each channel. The packet is sent to the dongle with the
+    /// scan request.
+    ///
+    /// The answer is read in a buffer of 63 bytes by default, one byte per
+    /// acking channel, see [Crazyradio::set_firmware_scan_buffer_size()]. If
+    /// more channels acked than fit in the buffer, the channels that fit are
+    /// returned and [FirmwareScan::truncated] is set: the remaining channels
+    /// can be found by scanning again from the channel after the last one
+    /// returned.
+    ///
+    /// The radio channel is set back to the current channel after the scan.
+    ///
+    /// Returns `Error::InvalidArgument` if `start` is greater than `stop` or
+    /// if `packet` is longer than 32 bytes, and
+    /// `Error::DongleVersionNotSupported` if the radio firmware does not
+    /// implement the firmware-side scan: it is implemented by the Crazyradio
+    /// PA firmware 0.5 and newer and by the Crazyradio 2.0, other firmware
+    /// stall the request.
+    pub fn scan_channels_firmware(
         &mut self,
         start: Channel,
         stop: Channel,
         packet: &[u8],
-    ) -> Result<Vec<Channel>> {
+    ) -> Result<FirmwareScan> {
+        if start > stop {
+            return Err(Error::InvalidArgument);
+        }
+        check_payload_length(packet)?;
+        if !firmware_supports_scan(self.firmware_version) {
+            return Err(Error::DongleVersionNotSupported);
+        }
+
+        match self.control_out(
+            UsbCommand::ScanChannels,
+            start.0 as u16,
+            stop.0 as u16,
+            packet,
+        ) {
+            Ok(_) => {}
+            Err(rusb::Error::Pipe) => return Err(Error::DongleVersionNotSupported),
+            Err(e) => return Err(e.into()),
+        }
+        let mut answer = vec![0u8; self.firmware_scan_buffer_size];
+        let received = self.control_in(UsbCommand::ScanChannels, 0, 0, &mut answer)?;
+
+        // The firmware leaves the radio on the last scanned channel
+        if self.inline_mode.is_off() {
+            self.control_out(UsbCommand::SetRadioChannel, self.channel.0 as u16, 0, &[])?;
+        }
+
+        parse_firmware_scan(&answer[..received], answer.len(), start, stop)
+    }
+
+    /// Sends a packet to a range of channel and returns a list of channel that acked
+    ///
+    /// The channels from `start` to `stop`, both included, are scanned.
+    /// Returns `Error::InvalidArgument` if `start` is greater than `stop`.
+    ///
+    /// Used to activally scann for receives on channels. This function sends
+    pub fn scan_channels(
+        &mut self,
+        start: Channel,
+        stop: Channel,
+        packet: &[u8],
+    ) -> Result<Vec<Channel>> {
+        self.scan_channels_cancellable(start, stop, packet, &AtomicBool::new(false))
+    }
+
+    /// Scan channels until `max_found` channels have acked
+    ///
+    /// Same as [Crazyradio::scan_channels()] but the scan stops as soon as
+    /// `max_found` channels have acked, which saves scanning the rest of the
+    /// range when the number of devices is known. Returns the channels found,
+    /// less than `max_found` if the end of the range is reached first.
+    pub fn scan_channels_until(
+        &mut self,
+        start: Channel,
+        stop: Channel,
+        packet: &[u8],
+        max_found: usize,
+    ) -> Result<Vec<Channel>> {
+        if start > stop {
+            return Err(Error::InvalidArgument);
+        }
+
+        let mut result = vec![];
         let mut ack_data = [0u8; 32];
-        let mut result: Vec<Channel> = vec![];
-        for ch in start.0..stop.0 + 1 {
-            let channel = Channel::from_number(ch).unwrap();
-            self.set_channel(channel)?;
-            let ack = self.send_packet(packet, &mut ack_data)?;
-            if ack.received {
+        for channel in Channel::range(start, stop) {
+            if result.len() >= max_found {
+                break;
+            }
+            self.set_scan_channel(channel)?;
+            if self.send_packet(packet, &mut ack_data)?.received {
+                result.push(channel);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Scan channels until `budget` has elapsed
+    ///
+    /// Same as [Crazyradio::scan_channels()] but the scan stops when `budget`
+    /// has elapsed, returning the channels found so far even if the range is
+    /// not fully scanned. This bounds the discovery time, for example in a user
+    /// interface that must stay responsive. The channel being probed when the
+    /// budget elapses is completed, so the scan can overrun the budget by the
+    /// time of one packet.
+    pub fn scan_channels_timeboxed(
+        &mut self,
+        start: Channel,
+        stop: Channel,
+        packet: &[u8],
+        budget: Duration,
+    ) -> Result<Vec<Channel>> {
+        if start > stop {
+            return Err(Error::InvalidArgument);
+        }
+
+        let started = Instant::now();
+        let mut result = vec![];
+        let mut ack_data = [0u8; 32];
+        for channel in Channel::range(start, stop) {
+            if started.elapsed() >= budget {
+                break;
+            }
+            self.set_scan_channel(channel)?;
+            if self.send_packet(packet, &mut ack_data)?.received {
+                result.push(channel);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Sends a packet to each channel of a list and returns the ones that acked
+    ///
+    /// Same as [Crazyradio::scan_channels()] but only the given `channels` are
+    /// probed, in the given order, instead of a range. This avoids spending
+    /// time on channels known to be unused, for example when a swarm only uses
+    /// a few reserved channels. The acking channels are returned in the order
+    /// of `channels`.
+    pub fn scan_channel_list(
+        &mut self,
+        channels: &[Channel],
+        packet: &[u8],
+    ) -> Result<Vec<Channel>> {
+        let mut result = vec![];
+        let mut ack_data = [0u8; 32];
+        for &channel in channels {
+            self.set_scan_channel(channel)?;
+            if self.send_packet(packet, &mut ack_data)?.received {
                 result.push(channel);
             }
         }
         Ok(result)
     }
 
+    /// Scan channels and return the ack of each channel that acked
+    ///
+    /// Same as [Crazyradio::scan_channels()] but the ack and its payload are
+    /// returned with each channel. The ack payload can identify the device
+    /// that answered.
+    pub fn scan_channels_detailed(
+        &mut self,
+        start: Channel,
+        stop: Channel,
+        packet: &[u8],
+    ) -> Result<Vec<ScanHit>> {
+        scan_hits_with(start, stop, &AtomicBool::new(false), |channel| {
+            self.set_scan_channel(channel)?;
+            let (ack, payload) = self.send_packet_borrowed(packet)?;
+            Ok(ack.received.then(|| ScanHit {
+                channel,
+                ack,
+                payload: payload.to_vec(),
+            }))
+        })
+    }
+
+    /// Scan channels keeping only the ones acked with a strong enough signal
+    ///
+    /// Same as [Crazyradio::scan_channels()] but a channel is only reported if
+    /// the RSSI of its ack is greater or equal to `min_rssi_dbm`, which
+    /// filters out weak spurious acks near the noise floor.
+    ///
+    /// The RSSI is only measured with inline mode [InlineMode::OnWithRssi] on
+    /// radios supporting it. Acks without RSSI measurement are not filtered:
+    /// on radios without RSSI support this behaves as
+    /// [Crazyradio::scan_channels()].
+    pub fn scan_channels_min_rssi(
+        &mut self,
+        start: Channel,
+        stop: Channel,
+        packet: &[u8],
+        min_rssi_dbm: i8,
+    ) -> Result<Vec<Channel>> {
+        let mut ack_data = [0u8; 32];
+        scan_channels_with(start, stop, &AtomicBool::new(false), |channel| {
+            self.set_scan_channel(channel)?;
+            let ack = self.send_packet(packet, &mut ack_data)?;
+            Ok(ack_meets_rssi(&ack, min_rssi_dbm))
+        })
+    }
+
+    /// Scan channels sending up to `repeats` packets on each channel
+    ///
+    /// Same as [Crazyradio::scan_channels()] but a channel is only considered
+    /// empty if none of the `repeats` packets sent on it is acked, which makes
+    /// the scan more reliable on a noisy link. `progress` is called after each
+    /// channel with the number of channels scanned and the total number of
+    /// channels, for example to update a progress bar. See
+    /// [Crazyradio::estimated_scan_duration()] to estimate the scan duration.
+    pub fn scan_channels_repeated(
+        &mut self,
+        start: Channel,
+        stop: Channel,
+        packet: &[u8],
+        repeats: usize,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<Vec<Channel>> {
+        let total = Channel::range(start, stop).count();
+        let mut scanned = 0;
+        let mut ack_data = [0u8; 32];
+        scan_channels_with(start, stop, &AtomicBool::new(false), |channel| {
+            self.set_scan_channel(channel)?;
+            let mut acked = false;
+            for _ in 0..repeats {
+                if self.send_packet(packet, &mut ack_data)?.received {
+                    acked = true;
+                    break;
+                }
+            }
+            scanned += 1;
+            progress(scanned, total);
+            Ok(acked)
+        })
+    }
+
+    /// Check that the radio works, for troubleshooting
+    ///
+    /// Runs the following steps and reports if each of them passed:
+    ///  - `serial`: reads the serial number, confirming the radio is open
+    ///  - `firmware version`: reads the device descriptor and checks that the
+    ///    firmware version is supported
+    ///  - `datarate 250K`, `datarate 1M` and `datarate 2M`: sets each datarate
+    ///  - `continuous carrier`: enables and disables the continuous carrier
+    ///  - `null packet`: sends a null CRTP packet (`[0xff]`) and reads the
+    ///    answer of the radio, it does not need to be acked
+    ///
+    /// All the steps are run even if one fails. The radio configuration is
+    /// restored afterward, an error is returned if restoring it fails.
+    ///
+    /// Note: the continuous carrier step emits a carrier for a short time.
+    pub fn self_test(&mut self) -> Result<SelfTestReport> {
+        let config = self.config();
+
+        let steps: [(&'static str, SelfTestFn<Self>); 7] = [
+            ("serial", |cr| cr.serial().map(|_| ())),
+            ("firmware version", |cr| {
+                let version = cr.device_handle.firmware_version()?;
+                if version < rusb::Version::from_bcd(0x0050) {
+                    return Err(Error::DongleVersionNotSupported);
+                }
+                Ok(())
+            }),
+            ("datarate 250K", |cr| cr.set_datarate(Datarate::Dr250K)),
+            ("datarate 1M", |cr| cr.set_datarate(Datarate::Dr1M)),
+            ("datarate 2M", |cr| cr.set_datarate(Datarate::Dr2M)),
+            ("continuous carrier", |cr| {
+                cr.set_cont_carrier(true)?;
+                cr.set_cont_carrier(false)
+            }),
+            ("null packet", |cr| {
+                let mut ack_data = [0u8; 32];
+                cr.send_packet(&[0xff], &mut ack_data).map(|_| ())
+            }),
+        ];
+        let steps = steps
+            .iter()
+            .map(|(name, step)| SelfTestStep {
+                name,
+                error: step(self).err(),
+            })
+            .collect();
+
+        self.reset_to(&config)?;
+        Ok(SelfTestReport { steps })
+    }
+
+    /// Monitor the RSSI of the link over a rolling window of samples
+    ///
+    /// Sends null CRTP packets (`[0xff]`) with the current configuration
+    /// until `stop` is set or sending fails. After each ack with an RSSI
+    /// measurement, `on_update` is called with the statistics of the last
+    /// `window` samples. Packets not acked, or acked without RSSI measurement,
+    /// do not update the statistics. The RSSI is only measured with inline
+    /// mode [InlineMode::OnWithRssi] on radios supporting it.
+    ///
+    /// Returns `Error::InvalidArgument` if `window` is 0.
+    pub fn rssi_monitor(
+        &mut self,
+        window: usize,
+        mut on_update: impl FnMut(RssiStats),
+        stop: &AtomicBool,
+    ) -> Result<()> {
+        if window == 0 {
+            return Err(Error::InvalidArgument);
+        }
+
+        let mut samples = RssiWindow::new(window);
+        let mut ack_data = [0u8; 32];
+        while !stop.load(Ordering::Relaxed) {
+            let ack = self.send_packet(&[0xff], &mut ack_data)?;
+            if let (true, Some(rssi_dbm)) = (ack.received, ack.rssi_dbm) {
+                on_update(samples.push(rssi_dbm));
+            }
+        }
+        Ok(())
+    }
+
+    /// Scan channels repeatedly and report the channels appearing and disappearing
+    ///
+    /// The channels from `start` to `stop`, both included, are scanned with
+    /// `payload` as with [Crazyradio::scan_channels()] every `interval`. When
+    /// the acking channels differ from the previous scan, `on_change` is
+    /// called with the channels added and removed. The first scan reports all
+    /// the acking channels as added. This allows to follow devices coming
+    /// online or dropping off, for example in a swarm dashboard.
+    ///
+    /// Runs until `stop_watch` is set, which is checked before each scan, or
+    /// until an error occurs.
+    pub fn scan_watch(
+        &mut self,
+        start: Channel,
+        stop: Channel,
+        payload: &[u8],
+        interval: Duration,
+        mut on_change: impl FnMut(&ScanDiff),
+        stop_watch: &AtomicBool,
+    ) -> Result<()> {
+        let mut previous = vec![];
+        while !stop_watch.load(Ordering::Relaxed) {
+            let current = self.scan_channels(start, stop, payload)?;
+            let diff = ScanDiff {
+                added: current
+                    .iter()
+                    .filter(|c| !previous.contains(*c))
+                    .copied()
+                    .collect(),
+                removed: previous
+                    .iter()
+                    .filter(|c| !current.contains(*c))
+                    .copied()
+                    .collect(),
+            };
+            if !diff.added.is_empty() || !diff.removed.is_empty() {
+                on_change(&diff);
+            }
+            previous = current;
+
+            if stop_watch.load(Ordering::Relaxed) {
+                break;
+            }
+            std::thread::sleep(interval);
+        }
+        Ok(())
+    }
+
+    /// Measure the round-trip time of sending a packet and receiving its ack
+    ///
+    /// Sends `samples` null CRTP packets (`[0xff]`) with the current
+    /// configuration and returns the minimum, average and maximum time between
+    /// starting to send a packet and getting its ack. This can be used to
+    /// schedule timing-sensitive sends ahead by the USB and radio latency.
+    ///
+    /// Returns Error::InvalidArgument if `samples` is 0.
+    pub fn calibrate_latency(&mut self, samples: usize) -> Result<LatencyStats> {
+        if samples == 0 {
+            return Err(Error::InvalidArgument);
+        }
+
+        let mut ack_data = [0u8; 32];
+        let mut min = Duration::MAX;
+        let mut max = Duration::ZERO;
+        let mut total = Duration::ZERO;
+        for _ in 0..samples {
+            let start = Instant::now();
+            self.send_packet(&[0xff], &mut ack_data)?;
+            let latency = start.elapsed();
+
+            min = min.min(latency);
+            max = max.max(latency);
+            total += latency;
+        }
+
+        Ok(LatencyStats {
+            min,
+            avg: total / samples as u32,
+            max,
+        })
+    }
+
+    /// Scan all datarates and channels and return the URI of the links found
+    ///
+    /// A null CRTP packet (`[0xff]`) is sent to the current address on each
+    /// channel at 250K, 1M and 2M. Each acked (channel, datarate) is returned
+    /// as a Crazyflie link URI like `radio://0/42/2M/E7E7E7E742`, where
+    /// `radio_index` is the first field of the URI.
+    ///
+    /// The radio is left on the last scanned channel and datarate.
+    pub fn scan_uris(&mut self, radio_index: usize) -> Result<Vec<String>> {
+        let mut uris = vec![];
+        for datarate in [Datarate::Dr250K, Datarate::Dr1M, Datarate::Dr2M] {
+            self.set_datarate(datarate)?;
+            let found = self.scan_channels(Channel(0), Channel(125), &[0xff])?;
+            uris.extend(
+                found
+                    .into_iter()
+                    .map(|channel| link_uri(radio_index, channel, datarate, &self.address)),
+            );
+        }
+        Ok(uris)
+    }
+
+    /// Scan channels with several radios in parallel
+    ///
+    /// The channels from `start` to `stop`, both included, are split in
+    /// contiguous ranges of about the same size, one per radio. Each radio
+    /// scans its range in its own thread with [Crazyradio::scan_channels()],
+    /// the radios must have been configured with the same datarate and
+    /// address. A single radio scans the whole range in the calling thread.
+    ///
+    /// Returns the sorted list of channels that acked. Returns
+    /// `Error::InvalidArgument` if `radios` is empty or `start` is greater
+    /// than `stop`, and the first error of a radio otherwise.
+    pub fn parallel_scan(
+        radios: &mut [Self],
+        start: Channel,
+        stop: Channel,
+        payload: &[u8],
+    ) -> Result<Vec<Channel>> {
+        if radios.is_empty() || start > stop {
+            return Err(Error::InvalidArgument);
+        }
+
+        let ranges = split_channels(start, stop, radios.len());
+        if let [(start, stop)] = ranges[..] {
+            return radios[0].scan_channels(start, stop, payload);
+        }
+
+        let results: Vec<Result<Vec<Channel>>> = std::thread::scope(|s| {
+            let scans: Vec<_> = radios
+                .iter_mut()
+                .zip(ranges)
+                .map(|(radio, (start, stop))| {
+                    s.spawn(move || radio.scan_channels(start, stop, payload))
+                })
+                .collect();
+            scans
+                .into_iter()
+                .map(|scan| scan.join().expect("Scan thread panicked"))
+                .collect()
+        });
+
+        let mut found = vec![];
+        for result in results {
+            found.extend(result?);
+        }
+        found.sort();
+        Ok(found)
+    }
+
+    /// Cancellable version of [Crazyradio::scan_channels()]
+    ///
+    /// `cancel` is checked before probing each channel. If it is set, the
+    /// scan stops and `Error::Cancelled` is returned, the channels found so
+    /// far are discarded. The radio is left on the last scanned channel.
+    pub fn scan_channels_cancellable(
+        &mut self,
+        start: Channel,
+        stop: Channel,
+        packet: &[u8],
+        cancel: &AtomicBool,
+    ) -> Result<Vec<Channel>> {
+        let mut ack_data = [0u8; 32];
+        scan_channels_with(start, stop, cancel, |channel| {
+            self.set_scan_channel(channel)?;
+            let ack = self.send_packet(packet, &mut ack_data)?;
+            Ok(ack.received)
+        })
+    }
+
+    /// Select the quietest channel out of `candidates` and configure the radio to it.
+    ///
+    /// Each candidate channel is probed by sending `probes` null CRTP packets
+    /// (`[0xff]`) to the current address. A probe counts as busy if the power
+    /// detector of the radio reported a signal stronger than -64dBm on the
+    /// channel while no ack was received: a received ack trips the power
+    /// detector by itself and is not counted. The channel with the least busy
+    /// probes is selected, the first candidate wins in case of a tie.
+    ///
+    /// This is a heuristic: it only detects traffic happening while probing
+    /// and can be used to avoid channels overlapping with a busy WiFi network
+    /// or another Crazyradio.
+    ///
+    /// Returns the selected channel, or Error::InvalidArgument if `candidates`
+    /// is empty.
+    pub fn auto_select_channel(
+        &mut self,
+        candidates: &[Channel],
+        probes: usize,
+    ) -> Result<Channel> {
+        let mut ack_data = [0u8; 32];
+        // The first channel wins in case of a tie
+        let mut quietest: Option<(Channel, usize)> = None;
+        for &channel in candidates {
+            self.set_channel(channel)?;
+            let mut busy = 0;
+            for _ in 0..probes {
+                let ack = self.send_packet(&[0xff], &mut ack_data)?;
+                if probe_is_busy(&ack) {
+                    busy += 1;
+                }
+            }
+            if quietest.is_none_or(|(_, least_busy)| busy < least_busy) {
+                quietest = Some((channel, busy));
+            }
+        }
+
+        let (channel, _) = quietest.ok_or(Error::InvalidArgument)?;
+        self.set_channel(channel)?;
+        Ok(channel)
+    }
+
+    /// Estimate the noise floor of `channel`
+    ///
+    /// Sends `samples` null CRTP packets (`[0xff]`) on `channel` and returns
+    /// the fraction of them, between 0.0 and 1.0, for which the power detector
+    /// of the radio reported a signal stronger than -64dBm. The radio does not
+    /// measure the noise in dBm, this is only an indication to compare
+    /// channels. A device acking on the current address also trips the power
+    /// detector, so the address should not be in use on the channel.
+    ///
+    /// The previous channel is restored before returning.
+    ///
+    /// Returns `Error::InvalidArgument` if `samples` is 0.
+    pub fn noise_floor(&mut self, channel: Channel, samples: usize) -> Result<f32> {
+        if samples == 0 {
+            return Err(Error::InvalidArgument);
+        }
+
+        let previous_channel = self.channel;
+        self.set_channel(channel)?;
+
+        let mut ack_data = [0u8; 32];
+        let mut tripped = 0;
+        let result = (0..samples).try_for_each(|_| {
+            if self.send_packet(&[0xff], &mut ack_data)?.power_detector {
+                tripped += 1;
+            }
+            Ok(())
+        });
+
+        self.set_channel(previous_channel)?;
+        result.map(|_| tripped as f32 / samples as f32)
+    }
+
     /// Launch the bootloader.
     ///
     /// Consumes the Crazyradio since it is not usable after that (it is in bootlaoder mode ...).
-    pub fn launch_bootloader(self) -> Result<()> {
-        self.device_handle.write_control(
-            0x40,
-            UsbCommand::LaunchBootloader as u8,
-            0,
-            0,
-            &[],
-            Duration::from_secs(1),
-        )?;
+    pub fn launch_bootloader(mut self) -> Result<()> {
+        self.control_out(UsbCommand::LaunchBootloader, 0, 0, &[])?;
         Ok(())
     }
 
@@ -552,17 +2504,60 @@ impl Crazyradio {
     /// In continious carrier mode, the radio will transmit a continious sine
     /// wave at the setup channel frequency using the setup transmit power.
     pub fn set_cont_carrier(&mut self, enable: bool) -> Result<()> {
-        self.device_handle.write_control(
-            0x40,
-            UsbCommand::SetContCarrier as u8,
-            enable as u16,
-            0,
-            &[],
-            Duration::from_secs(1),
-        )?;
+        self.control_out(UsbCommand::SetContCarrier, enable as u16, 0, &[])?;
+        self.cont_carrier = enable;
         Ok(())
     }
 
+    /// Enable continious carrier mode until the returned guard is dropped.
+    ///
+    /// The carrier is disabled when the [CarrierGuard] is dropped, including
+    /// when the scope is left by an error or a panic, so that the radio is
+    /// not left transmitting by mistake.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # fn main() -> Result<(), crazyradio::Error> {
+    /// let mut cr = crazyradio::Crazyradio::open_first()?;
+    /// {
+    ///     let _carrier = cr.continuous_carrier()?;
+    ///     std::thread::sleep(std::time::Duration::from_secs(1));
+    /// }
+    /// // The carrier is disabled here
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn continuous_carrier(&mut self) -> Result<CarrierGuard<'_, C>> {
+        self.set_cont_carrier(true)?;
+
+        Ok(CarrierGuard {
+            radio: self,
+            disabled: false,
+        })
+    }
+
+    /// Return true if the acks report their RSSI in [Ack::rssi_dbm]
+    ///
+    /// The RSSI is measured by Crazyradio 2.0 with firmware 5.3 or newer when
+    /// inline mode is [InlineMode::OnWithRssi], which is selected when opening
+    /// the radio if supported. Otherwise [Ack::rssi_dbm] is always `None`. This
+    /// allows to check for RSSI support before sending packets.
+    pub fn supports_rssi(&self) -> bool {
+        self.inline_mode == InlineMode::OnWithRssi && self.supports_rssi_firmware()
+    }
+
+    fn supports_rssi_firmware(&self) -> bool {
+        firmware_supports_rssi(self.firmware_version)
+    }
+
+    /// Return true if continious carrier mode is enabled
+    ///
+    /// This is the state set by the last call to
+    /// [Crazyradio::set_cont_carrier()], the radio is not queried.
+    pub fn cont_carrier_enabled(&self) -> bool {
+        self.cont_carrier
+    }
+
     /// Set inline-settings USB protocol mode
     ///
     /// When this mode is enabled, setting channel, datarate, address and
@@ -573,18 +2568,17 @@ impl Crazyradio {
     /// This mode, if available, is activated by default when creating the Crazyradio
     /// object.
     ///
-    /// This mode is only available with Crazyradio 2.0+
+    /// This mode is only available with Crazyradio 2.0+. [InlineMode::OnWithRssi]
+    /// requires firmware 5.3 or newer, `Error::DongleVersionNotSupported` is
+    /// returned with older firmware.
     pub fn set_inline_mode(&mut self, mode: InlineMode) -> Result<()> {
+        // Older firmware would not send the RSSI expected in the answers
+        if mode == InlineMode::OnWithRssi && !self.supports_rssi_firmware() {
+            return Err(Error::DongleVersionNotSupported);
+        }
         let setting = mode as u16;
 
-        self.device_handle.write_control(
-            0x40,
-            UsbCommand::SetInlineMode as u8,
-            setting,
-            0,
-            &[],
-            Duration::from_secs(1),
-        )?;
+        self.control_out(UsbCommand::SetInlineMode, setting, 0, &[])?;
         self.inline_mode = mode;
 
         Ok(())
@@ -597,24 +2591,37 @@ impl Crazyradio {
         packet_loss_percent: u8,
         ack_loss_percent: u8,
     ) -> Result<()> {
-        if self.device_desciptor.device_version() < rusb::Version::from_bcd(0x0500) {
+        if self.firmware_version < rusb::Version::from_bcd(0x0500) {
             return Err(Error::DongleVersionNotSupported);
         }
 
-        if packet_loss_percent <= 100 && ack_loss_percent <= 100 {
-            let data = [packet_loss_percent, ack_loss_percent];
-            self.device_handle.write_control(
-                0x40,
-                UsbCommand::SetPacketLossSimulation as u8,
-                0,
-                0,
-                &data,
-                Duration::from_secs(1),
-            )?;
-            Ok(())
-        } else {
-            Err(Error::InvalidArgument)
-        }
+        let mut packet_loss = self.packet_loss;
+        set_packet_loss_with(
+            &mut packet_loss,
+            packet_loss_percent,
+            ack_loss_percent,
+            |data| {
+                self.control_out(UsbCommand::SetPacketLossSimulation, 0, 0, &data)?;
+                Ok(())
+            },
+        )?;
+        self.packet_loss = packet_loss;
+        Ok(())
+    }
+
+    /// Return the simulated packet and ack loss, in percent
+    ///
+    /// This is the state set by the last call to
+    /// [Crazyradio::set_packet_loss_simulation()], the radio is not queried.
+    pub fn packet_loss_simulation(&self) -> (u8, u8) {
+        self.packet_loss
+    }
+
+    /// Disable packet loss simulation
+    ///
+    /// Same as `set_packet_loss_simulation(0, 0)`.
+    pub fn clear_packet_loss_simulation(&mut self) -> Result<()> {
+        self.set_packet_loss_simulation(0, 0)
     }
 
     /// Enter sniffer mode (continuous RX).
@@ -643,24 +2650,10 @@ impl Crazyradio {
             self.cache_settings = saved_cache_settings;
             // Flush ack_enable directly — set_ack_enable would skip the USB
             // transfer because the cached value already matches.
-            self.device_handle.write_control(
-                0x40,
-                UsbCommand::AckEnable as u8,
-                self.ack_enable as u16,
-                0,
-                &[],
-                Duration::from_secs(1),
-            )?;
-        }
-
-        self.device_handle.write_control(
-            0x40,
-            UsbCommand::SetRadioMode as u8,
-            1,
-            0,
-            &[],
-            Duration::from_secs(1),
-        )?;
+            self.control_out(UsbCommand::AckEnable, self.ack_enable as u16, 0, &[])?;
+        }
+
+        self.control_out(UsbCommand::SetRadioMode, 1, 0, &[])?;
         self.sniffer_mode = true;
         Ok(())
     }
@@ -669,14 +2662,7 @@ impl Crazyradio {
     ///
     /// Re-enables inline mode if it was active before entering sniffer mode.
     pub fn exit_sniffer_mode(&mut self) -> Result<()> {
-        self.device_handle.write_control(
-            0x40,
-            UsbCommand::SetRadioMode as u8,
-            0,
-            0,
-            &[],
-            Duration::from_secs(1),
-        )?;
+        self.control_out(UsbCommand::SetRadioMode, 0, 0, &[])?;
         self.sniffer_mode = false;
 
         // Drain any leftover sniffer packets from the USB IN endpoint.
@@ -685,7 +2671,7 @@ impl Crazyradio {
         let mut drain_buf = [0u8; 64];
         while self
             .device_handle
-            .read_bulk(0x81, &mut drain_buf, Duration::from_millis(10))
+            .read_bulk(self.endpoint_in, &mut drain_buf, Duration::from_millis(10))
             .is_ok()
         {}
 
@@ -707,14 +2693,7 @@ impl Crazyradio {
         if pipe > 1 {
             return Err(Error::InvalidArgument);
         }
-        self.device_handle.write_control(
-            0x40,
-            UsbCommand::SetSnifferAddress as u8,
-            pipe as u16,
-            0,
-            address,
-            Duration::from_secs(1),
-        )?;
+        self.control_out(UsbCommand::SetSnifferAddress, pipe as u16, 0, address)?;
         Ok(())
     }
 
@@ -722,14 +2701,7 @@ impl Crazyradio {
     /// mode was last entered.
     pub fn get_sniffer_drop_count(&mut self) -> Result<u32> {
         let mut buf = [0u8; 4];
-        self.device_handle.read_control(
-            0xC0,
-            UsbCommand::GetSnifferDropCount as u8,
-            0,
-            0,
-            &mut buf,
-            Duration::from_secs(1),
-        )?;
+        self.control_in(UsbCommand::GetSnifferDropCount, 0, 0, &mut buf)?;
         Ok(u32::from_le_bytes(buf))
     }
 
@@ -759,7 +2731,7 @@ impl Crazyradio {
         }
 
         let mut buf = [0u8; 70]; // 7 header + 63 max payload
-        let received = match self.device_handle.read_bulk(0x81, &mut buf, timeout) {
+        let received = match self.bulk_in(&mut buf, timeout) {
             Ok(n) => n,
             Err(rusb::Error::Timeout) => return Ok(None),
             Err(e) => return Err(e.into()),
@@ -793,15 +2765,52 @@ impl Crazyradio {
         }))
     }
 
-    /// Send a broadcast (no-ack) packet while in sniffer mode.
-    ///
-    /// The packet is sent using the current channel, datarate, and the
-    /// given address. The radio briefly leaves RX mode during TX (~1ms),
-    /// so incoming packets during this window will be missed.
+    /// Passively listen to the packets sent to `address` on `channel`
     ///
-    /// No response is sent on the IN endpoint.
+    /// Configures the channel and address, enters sniffer mode and calls
+    /// `on_packet` with the payload of each packet received until `stop` is
+    /// set. `stop` is checked at least every 100ms. Sniffer mode is exited
+    /// before returning, the datarate in use is the one currently configured.
     ///
-    /// # Arguments
+    /// This requires a firmware supporting sniffer mode, older firmware
+    /// reject entering sniffer mode with a USB error.
+    pub fn sniff(
+        &mut self,
+        channel: Channel,
+        address: [u8; 5],
+        mut on_packet: impl FnMut(&[u8]),
+        stop: &AtomicBool,
+    ) -> Result<()> {
+        self.set_channel(channel)?;
+        self.set_address(&address)?;
+        self.enter_sniffer_mode()?;
+
+        // Sniffer mode is exited even if receiving fails
+        let mut payload = [0u8; 63];
+        let mut result = Ok(());
+        while !stop.load(Ordering::Relaxed) {
+            match self.receive_sniffer_packet(&mut payload, Duration::from_millis(100)) {
+                Ok(Some(packet)) => on_packet(&payload[..packet.length.min(payload.len())]),
+                Ok(None) => (),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        result.and(self.exit_sniffer_mode())
+    }
+
+    /// Send a broadcast (no-ack) packet while in sniffer mode.
+    ///
+    /// The packet is sent using the current channel, datarate, and the
+    /// given address. The radio briefly leaves RX mode during TX (~1ms),
+    /// so incoming packets during this window will be missed.
+    ///
+    /// No response is sent on the IN endpoint.
+    ///
+    /// # Arguments
     ///
     ///  * `address`: 5-byte destination address for the broadcast.
     ///  * `data`: 1 to 63 bytes of raw ESB payload to broadcast.
@@ -825,8 +2834,7 @@ impl Crazyradio {
         let mut buf = Vec::with_capacity(5 + data.len());
         buf.extend_from_slice(address);
         buf.extend_from_slice(data);
-        self.device_handle
-            .write_bulk(0x01, &buf, Duration::from_secs(1))?;
+        self.bulk_out(&buf, Duration::from_secs(1))?;
 
         Ok(())
     }
@@ -835,16 +2843,37 @@ impl Crazyradio {
     ///
     /// # Arguments
     ///
-    ///  * `data`: Up to 32 bytes of data to be send.
+    ///  * `data`: 1 to 32 bytes of data to be send. An empty packet is
+    ///    rejected with `Error::InvalidArgument`: it cannot be told apart
+    ///    from no packet by the radio when inline mode is off. Longer data
+    ///    panics in debug builds.
     ///  * `ack_data`: Buffer to hold the data received from the ack packet
-    ///                payload. The ack payload can be up to 32 bytes, if this
-    ///                buffer length is lower than 32 bytes the ack data might
-    ///                be truncated. The length of the ack payload is returned
-    ///                in Ack::length.
+    ///    payload. The ack payload can be up to 32 bytes, if this
+    ///    buffer length is lower than 32 bytes the ack data might
+    ///    be truncated. The length of the ack payload is returned
+    ///    in Ack::length.
     pub fn send_packet(&mut self, data: &[u8], ack_data: &mut [u8]) -> Result<Ack> {
+        self.submit_packet(data)?;
+        self.fetch_ack(ack_data)
+    }
+
+    /// Send a data packet without waiting for its ack.
+    ///
+    /// This only does the USB OUT transfer of [Crazyradio::send_packet()], the
+    /// ack must then be read with [Crazyradio::fetch_ack()]. This allows to do
+    /// other work while the radio is sending the packet.
+    ///
+    /// **Warning**: each call must be paired with exactly one call to
+    /// [Crazyradio::fetch_ack()] before the next packet is submitted or any
+    /// other radio function is called, otherwise the acks get out of sync with
+    /// the packets.
+    pub fn submit_packet(&mut self, data: &[u8]) -> Result<()> {
         if self.sniffer_mode {
             return Err(Error::InvalidArgument);
         }
+        debug_assert_payload_length(data);
+        check_packet_data(data)?;
+        check_ack_mode(self.strict_ack_check, self.ack_enable, true)?;
 
         // Capture TX packet
         #[cfg(feature = "packet_capture")]
@@ -856,33 +2885,147 @@ impl Crazyradio {
             data,
         );
 
-        let ack = if self.inline_mode.is_on() {
-            self.send_inline(data, Some(ack_data))?
+        if self.inline_mode.is_on() {
+            self.write_inline(data)?;
         } else {
-            self.device_handle
-                .write_bulk(0x01, data, Duration::from_secs(1))?;
-            let mut received_data = [0u8; 33];
-            let received =
-                self.device_handle
-                    .read_bulk(0x81, &mut received_data, Duration::from_secs(1))?;
-
-            if ack_data.len() <= 32 {
-                ack_data.copy_from_slice(&received_data[1..ack_data.len() + 1]);
-            } else {
-                ack_data
-                    .split_at_mut(32)
-                    .0
-                    .copy_from_slice(&received_data[1..33]);
-            }
+            self.bulk_out(data, Duration::from_secs(1))?;
+        }
 
-            Ack {
-                received: received_data[0] & 0x01 != 0,
-                power_detector: received_data[0] & 0x02 != 0,
-                retry: ((received_data[0] & 0xf0) >> 4) as usize,
-                length: received - 1,
-                rssi_dbm: None,
+        Ok(())
+    }
+
+    /// Read the ack of a packet sent with [Crazyradio::submit_packet()].
+    ///
+    /// Blocks until the radio reports the ack. See
+    /// [Crazyradio::send_packet()] for the meaning of `ack_data`.
+    pub fn fetch_ack(&mut self, ack_data: &mut [u8]) -> Result<Ack> {
+        if self.sniffer_mode {
+            return Err(Error::InvalidArgument);
+        }
+
+        let (ack, payload) = self.receive_ack()?;
+        self.record_ack(&ack);
+
+        copy_ack_payload(&self.rx_buffer[payload], ack_data);
+
+        Ok(ack)
+    }
+
+    /// Send a data packet and return the answer of the radio without decoding it.
+    ///
+    /// Unlike [Crazyradio::send_packet()], which decodes the status byte at the
+    /// start of the answer into an [Ack] and only returns the ack payload
+    /// after it, this copies the whole answer received from the radio to
+    /// `out`, status byte included, and leaves its interpretation to the
+    /// caller. This is intended for experimental firmware protocols using the
+    /// first byte for data.
+    ///
+    /// With inline mode off the answer is `[status, payload...]`, with inline
+    /// mode on it starts with the inline header instead. The answer can be up
+    /// to one USB packet, see [Crazyradio::max_packet_size()], it is truncated
+    /// if `out` is shorter. Returns the length of the answer.
+    pub fn send_packet_raw(&mut self, data: &[u8], out: &mut [u8]) -> Result<usize> {
+        self.submit_packet(data)?;
+
+        let received = self.bulk_in_rx_buffer(Duration::from_secs(1))?;
+
+        Ok(copy_raw_answer(&self.rx_buffer[..received], out))
+    }
+
+    /// Write raw bytes to the bulk OUT endpoint (experimental)
+    ///
+    /// The bytes are sent as is, without inline header, to help bringing up
+    /// new firmware protocols. Returns the number of bytes written.
+    ///
+    /// Warning: the radio answers are not read by this function. Mixing raw
+    /// transfers with the packet functions, like [Crazyradio::send_packet()],
+    /// can make them read an answer that does not belong to their packet.
+    /// Read the answers with [Crazyradio::raw_bulk_read()] or call
+    /// [Crazyradio::reset()] before going back to the packet functions.
+    pub fn raw_bulk_write(&mut self, data: &[u8]) -> Result<usize> {
+        Ok(self.bulk_out(data, Duration::from_secs(1))?)
+    }
+
+    /// Read raw bytes from the bulk IN endpoint (experimental)
+    ///
+    /// Returns the number of bytes read in `buf`, the answer is not decoded.
+    /// See the warning of [Crazyradio::raw_bulk_write()].
+    pub fn raw_bulk_read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(self.bulk_in(buf, Duration::from_secs(1))?)
+    }
+
+    /// Send a data packet and wait for its ack until `deadline`.
+    ///
+    /// Same as [Crazyradio::send_packet()] but the time left until `deadline`
+    /// is used as USB timeout to read the ack, instead of the default one
+    /// second. Returns `Error::Timeout` if the deadline is reached before the
+    /// ack is received, or if it has already passed, in which case the packet
+    /// is not sent.
+    ///
+    /// An ack read after a timeout might still be queued in the radio, see
+    /// [Crazyradio::set_channel_flushed()].
+    pub fn send_packet_deadline(
+        &mut self,
+        data: &[u8],
+        ack_data: &mut [u8],
+        deadline: Instant,
+    ) -> Result<Ack> {
+        time_until(deadline, Instant::now())?;
+        self.submit_packet(data)?;
+
+        let timeout = time_until(deadline, Instant::now())?;
+        let (ack, payload) = match self.receive_ack_timeout(timeout) {
+            Err(Error::UsbError(rusb::Error::Timeout)) => return Err(Error::Timeout),
+            result => result?,
+        };
+        self.record_ack(&ack);
+
+        copy_ack_payload(&self.rx_buffer[payload], ack_data);
+
+        Ok(ack)
+    }
+
+    /// Send a data packet and return the ack payload without copying it.
+    ///
+    /// Same as [Crazyradio::send_packet()] but instead of copying the ack
+    /// payload to a buffer provided by the caller, this function returns a
+    /// slice of the radio internal receive buffer. The slice length is the ack
+    /// payload length.
+    ///
+    /// The slice borrows the radio, it is only valid until the next call to a
+    /// Crazyradio function which overwrites the buffer.
+    pub fn send_packet_borrowed(&mut self, data: &[u8]) -> Result<(Ack, &[u8])> {
+        self.submit_packet(data)?;
+        let (ack, payload) = self.receive_ack()?;
+        self.record_ack(&ack);
+
+        Ok((ack, &self.rx_buffer[payload]))
+    }
+
+    // Read the answer to a sent packet in rx_buffer. Returns the decoded ack
+    // and the range of the ack payload in rx_buffer.
+    fn receive_ack(&mut self) -> Result<(Ack, Range<usize>)> {
+        self.receive_ack_timeout(Duration::from_secs(1))
+    }
+
+    // Same as receive_ack() waiting at most `timeout` for each USB read
+    fn receive_ack_timeout(&mut self, timeout: Duration) -> Result<(Ack, Range<usize>)> {
+        let received = if self.inline_mode.is_on() {
+            // Read response, discarding any stale sniffer packets that may still
+            // be queued due to the race between firmware mode switch and packet
+            // reception. PTX ack responses are at most 35 bytes (3-byte header +
+            // 32-byte max payload), so anything larger is a stale sniffer packet.
+            loop {
+                let size = self.bulk_in_rx_buffer(timeout)?;
+                if size <= MAX_INLINE_ACK_SIZE {
+                    break size;
+                }
+                // Stale sniffer packet, discard and read again
             }
+        } else {
+            self.receive_answer(MAX_ACK_SIZE, timeout)?
         };
+        let (ack, payload) = decode_answer(&self.rx_buffer[..received], self.inline_mode)?;
 
         // Capture RX packet (ACK payload)
         #[cfg(feature = "packet_capture")]
@@ -892,22 +3035,182 @@ impl Crazyradio {
                 self.channel.into(),
                 &self.address,
                 &self.serial,
-                &ack_data[..ack.length.min(ack_data.len())],
+                &self.rx_buffer[payload.clone()],
             );
         }
 
-        Ok(ack)
+        Ok((ack, payload))
+    }
+
+    // Read the answer to a packet sent with inline mode off in rx_buffer.
+    // Returns its size, which is checked to be at most `max` bytes.
+    fn receive_answer(&mut self, max: usize, timeout: Duration) -> Result<usize> {
+        let received = self.bulk_in_rx_buffer(timeout)?;
+        check_read_size(received, max)
+    }
+
+    /// Send a data packet to `address` and restore the current address afterward.
+    ///
+    /// This is a shortcut for setting the address, calling
+    /// [Crazyradio::send_packet()] and setting back the previous address. The
+    /// previous address is restored even if sending the packet fails.
+    pub fn send_packet_to(
+        &mut self,
+        address: [u8; 5],
+        data: &[u8],
+        ack_data: &mut [u8],
+    ) -> Result<Ack> {
+        let previous_address = self.address;
+        with_temporary_setting(
+            self,
+            |cr| cr.set_address(&address),
+            |cr| cr.send_packet(data, ack_data),
+            |cr| cr.set_address(&previous_address),
+        )
+    }
+
+    /// Check that the radio works and that a device answers
+    ///
+    /// Sends a null CRTP packet (`[0xff]`) with the current configuration and
+    /// returns whether it was acked. This is a quick liveness check of the
+    /// dongle and of the link before starting a long-running loop: an error
+    /// means that the dongle does not respond and `false` that no device
+    /// answered on the current channel and address.
+    ///
+    /// No setting is changed, the radio is left in the same state.
+    pub fn ping(&mut self) -> Result<bool> {
+        ping_with(|data, ack_data| self.send_packet(data, ack_data))
+    }
+
+    /// Check if a device answers on one datarate, channel and address
+    ///
+    /// Sets `datarate`, `channel` and `address` with acks enabled, sends
+    /// `payload` once with [Crazyradio::send_packet()] and returns whether it
+    /// was acked. The previous configuration is restored afterward, even if
+    /// sending fails. This answers if a device is at one exact link address,
+    /// like `radio://0/80/2M/E7E7E7E7E7`, without scanning.
+    ///
+    /// The settings that differ from the current configuration are sent to the
+    /// radio, see [Crazyradio::reset_to()].
+    pub fn probe(
+        &mut self,
+        datarate: Datarate,
+        channel: Channel,
+        address: [u8; 5],
+        payload: &[u8],
+    ) -> Result<bool> {
+        let probe = RadioConfig {
+            datarate,
+            channel,
+            address,
+            ack_enable: true,
+            ..self.config()
+        };
+
+        let mut ack_data = [0u8; 32];
+        self.with_config_restored(|cr| {
+            cr.reset_to(&probe)?;
+            Ok(cr.send_packet(payload, &mut ack_data)?.received)
+        })
+    }
+
+    // Run `f` and set back the current configuration afterward, even if `f`
+    // failed since it may have changed some settings before failing. Only the
+    // settings changed by `f` are sent, see reset_to().
+    fn with_config_restored<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let previous = self.config();
+        with_temporary_setting(self, |_| Ok(()), f, |cr| cr.reset_to(&previous))
+    }
+
+    /// Send a data packet without retries and restore the retry count afterward.
+    ///
+    /// The auto retry count is set to 0 with [Crazyradio::set_arc()], the
+    /// packet is sent with [Crazyradio::send_packet()] and the previous retry
+    /// count is set back, even if sending fails. The packet is sent only once:
+    /// if it is lost the send fails fast, which suits latency-critical
+    /// commands for which a late packet is worse than a lost one.
+    ///
+    /// Setting and restoring the retry count costs two extra USB control
+    /// transfers. To send many packets without retries, set the retry count
+    /// once with [Crazyradio::set_arc()] instead.
+    pub fn send_packet_no_retry(&mut self, data: &[u8], ack_data: &mut [u8]) -> Result<Ack> {
+        self.with_config_restored(|cr| {
+            cr.set_arc(0)?;
+            cr.send_packet(data, ack_data)
+        })
+    }
+
+    /// Send one broadcast packet and restore the unicast settings
+    ///
+    /// Sets `channel` and `address`, disables ack, sends `payload` with
+    /// [Crazyradio::send_packet_no_ack()] and sets back the previous channel,
+    /// address and ack enable settings, even if sending fails. This is a
+    /// shortcut for sending a single command to a swarm between unicast
+    /// packets, use [Crazyradio::enter_broadcast_mode()] to send several
+    /// broadcast packets.
+    pub fn broadcast_once(
+        &mut self,
+        channel: Channel,
+        address: [u8; 5],
+        payload: &[u8],
+    ) -> Result<()> {
+        self.with_config_restored(|cr| {
+            cr.set_channel(channel)?;
+            cr.set_address(&address)?;
+            cr.set_ack_enable(false)?;
+            cr.send_packet_no_ack(payload)
+        })
+    }
+
+    /// Set a broadcast `address` and disable ack until the returned guard is dropped.
+    ///
+    /// The address and ack enable settings are restored to their previous
+    /// values when the [BroadcastGuard] is dropped, so that they cannot get out
+    /// of sync. While the guard is held, packets are sent with
+    /// [BroadcastGuard::send_packet_no_ack()].
+    ///
+    /// Example:
+    /// ```no_run
+    /// # fn main() -> Result<(), crazyradio::Error> {
+    /// let mut cr = crazyradio::Crazyradio::open_first()?;
+    /// {
+    ///     let mut broadcast = cr.enter_broadcast_mode([0xff, 0xe7, 0xe7, 0xe7, 0xe7])?;
+    ///     broadcast.send_packet_no_ack(&[0xff])?;
+    /// }
+    /// // Previous address and ack enable are restored here
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn enter_broadcast_mode(&mut self, address: [u8; 5]) -> Result<BroadcastGuard<'_, C>> {
+        let previous_address = self.address;
+        let previous_ack_enable = self.ack_enable;
+
+        self.set_address(&address)?;
+        if let Err(e) = self.set_ack_enable(false) {
+            let _ = self.set_address(&previous_address);
+            return Err(e);
+        }
+
+        Ok(BroadcastGuard {
+            radio: self,
+            previous_address,
+            previous_ack_enable,
+            restored: false,
+        })
     }
 
     /// Send a data packet without caring for Ack (for broadcast communication).
     ///
     /// # Arguments
     ///
-    ///  * `data`: Up to 32 bytes of data to be send.
+    ///  * `data`: Up to 32 bytes of data to be send. Longer data panics in
+    ///    debug builds.
     pub fn send_packet_no_ack(&mut self, data: &[u8]) -> Result<()> {
         if self.sniffer_mode {
             return Err(Error::InvalidArgument);
         }
+        debug_assert_payload_length(data);
+        check_ack_mode(self.strict_ack_check, self.ack_enable, false)?;
 
         // Capture TX packet
         #[cfg(feature = "packet_capture")]
@@ -920,29 +3223,142 @@ impl Crazyradio {
         );
 
         if self.inline_mode.is_on() {
-            self.send_inline(data, None)?;
+            // The radio answers inline packets even when not waiting for an ack
+            self.write_inline(data)?;
+            self.receive_ack()?;
         } else {
-            self.device_handle
-                .write_bulk(0x01, data, Duration::from_secs(1))?;
+            self.bulk_out(data, Duration::from_secs(1))?;
         }
 
         Ok(())
     }
 
-    fn send_inline(&mut self, data: &[u8], ack_data: Option<&mut [u8]>) -> Result<Ack> {
-        const OUT_HEADER_LENGTH: usize = 8;
-        const IN_HEADER_LENGTH: usize = 2;
-        const IN_HEADER_RSSI_LENGTH: usize = 3;
+    /// Send the same data packet `count` times without caring for Ack.
+    ///
+    /// Since there is no retry for broadcast packets, sending them a couple
+    /// of times increases the chance that all receivers get them. The packets
+    /// are spaced by `interval`, there is no wait after the last one.
+    ///
+    /// # Arguments
+    ///
+    ///  * `data`: Up to 32 bytes of data to be send.
+    ///  * `count`: Number of time the packet is sent.
+    ///  * `interval`: Time to wait between two packets.
+    pub fn send_packet_no_ack_repeated(
+        &mut self,
+        data: &[u8],
+        count: usize,
+        interval: Duration,
+    ) -> Result<()> {
+        for i in 0..count {
+            if i > 0 {
+                std::thread::sleep(interval);
+            }
+            self.send_packet_no_ack(data)?;
+        }
+        Ok(())
+    }
 
-        const OUT_FIELD2_ACK_ENABLE: u8 = 0x10;
+    /// Send a data packet, sending at most `max_pps` packets per second
+    ///
+    /// Sleeps as needed so that two packets sent with this function are
+    /// spaced by at least `1 / max_pps` seconds, to avoid flooding a crowded
+    /// channel or the USB bus in a tight send loop. A `max_pps` of 0 means no
+    /// limit.
+    ///
+    /// The time of the last packet is kept in the radio object, the limit
+    /// applies to all the calls to this function on this radio. Packets sent
+    /// with the other functions are not limited nor accounted.
+    ///
+    /// # Arguments
+    ///
+    ///  * `data`: Up to 32 bytes of data to be send.
+    ///  * `ack_data`: Buffer to hold the data received from the ack packet
+    ///    payload, see [Crazyradio::send_packet()].
+    ///  * `max_pps`: Maximum number of packets per second.
+    pub fn send_packet_throttled(
+        &mut self,
+        data: &[u8],
+        ack_data: &mut [u8],
+        max_pps: u32,
+    ) -> Result<Ack> {
+        self.rate_limiter.wait(max_pps);
+        self.send_packet(data, ack_data)
+    }
+
+    /// Send a data packet hopping over `channels` until it is acked.
+    ///
+    /// The packet is sent on each channel of the sequence in turn, stopping at
+    /// the first channel that acks. Returns this channel and its ack, or the
+    /// last channel of the sequence and its (not received) ack if none acked.
+    /// The radio is left on the returned channel.
+    ///
+    /// Returns `Error::InvalidArgument` if `channels` is empty.
+    ///
+    /// # Arguments
+    ///
+    ///  * `channels`: Hopping sequence to try, in order.
+    ///  * `data`: Up to 32 bytes of data to be send.
+    ///  * `ack_data`: Buffer to hold the data received from the ack packet
+    ///    payload, see [Crazyradio::send_packet()].
+    pub fn send_packet_hopping(
+        &mut self,
+        channels: &[Channel],
+        data: &[u8],
+        ack_data: &mut [u8],
+    ) -> Result<(Channel, Ack)> {
+        let mut last = None;
+        for &channel in channels {
+            self.set_channel(channel)?;
+            let ack = self.send_packet(data, ack_data)?;
+            if ack.received {
+                return Ok((channel, ack));
+            }
+            last = Some((channel, ack));
+        }
+        last.ok_or(Error::InvalidArgument)
+    }
+
+    /// Send a data packet, stepping down the datarate if the link degrades
+    ///
+    /// The number of packets lost in a row is tracked across calls. When more
+    /// than 3 packets in a row are not acked, the datarate is stepped down
+    /// (2M, then 1M, then 250K) and the packet is sent again. Any acked packet
+    /// resets the count, the datarate is never stepped up again
+    /// automatically.
+    ///
+    /// The datarate that succeeded is left configured and can be read back
+    /// with [Crazyradio::config()]. The count is reset by
+    /// [Crazyradio::reset()].
+    ///
+    /// # Arguments
+    ///
+    ///  * `data`: Up to 32 bytes of data to be send.
+    ///  * `ack_data`: Buffer to hold the data received from the ack packet
+    ///    payload, see [Crazyradio::send_packet()].
+    pub fn send_packet_adaptive(&mut self, data: &[u8], ack_data: &mut [u8]) -> Result<Ack> {
+        loop {
+            let ack = self.send_packet(data, ack_data)?;
+            if ack.received {
+                self.adaptive_no_acks = 0;
+                return Ok(ack);
+            }
+
+            self.adaptive_no_acks += 1;
+            match slower_datarate(self.datarate) {
+                Some(slower) if self.adaptive_no_acks > ADAPTIVE_NO_ACK_THRESHOLD => {
+                    self.set_datarate(slower)?;
+                    self.adaptive_no_acks = 0;
+                }
+                _ => return Ok(ack),
+            }
+        }
+    }
 
-        const IN_HEADER_ACK_RECEIVED: u8 = 0x01;
-        const IN_HEADER_POWER_DETECTOR: u8 = 0x02;
-        const _IN_HEADER_INVALID_SETTING: u8 = 0x04;
-        const IN_HEADER_RETRY_MASK: u8 = 0xf0;
-        const IN_HEADER_RETRY_SHIFT: u8 = 4;
+    fn write_inline(&mut self, data: &[u8]) -> Result<()> {
+        const OUT_HEADER_LENGTH: usize = 8;
 
-        const IN_HEADER_RSSI: usize = 2;
+        const OUT_FIELD2_ACK_ENABLE: u8 = 0x10;
 
         // Assemble out command
         let mut command = vec![];
@@ -954,67 +3370,41 @@ impl Crazyradio {
         command.push(field2);
         command.push(self.channel.into());
         command.extend_from_slice(&self.address);
-        command.extend_from_slice(&data);
-
-        let mut answer = [0u8; 64];
-        self.device_handle
-            .write_bulk(0x01, &command, Duration::from_secs(1))?;
-
-        // Read response, discarding any stale sniffer packets that may still
-        // be queued due to the race between firmware mode switch and packet
-        // reception. PTX ack responses are at most 35 bytes (3-byte header +
-        // 32-byte max payload), so anything larger is a stale sniffer packet.
-        const MAX_INLINE_ACK_SIZE: usize = 35;
-        let answer_size = loop {
-            let size = self
-                .device_handle
-                .read_bulk(0x81, &mut answer, Duration::from_secs(1))?;
-            if size <= MAX_INLINE_ACK_SIZE {
-                break size;
-            }
-            // Stale sniffer packet, discard and read again
-        };
-
-        let header_length = match self.inline_mode {
-            InlineMode::On => IN_HEADER_LENGTH,
-            InlineMode::OnWithRssi => IN_HEADER_RSSI_LENGTH,
-            InlineMode::Off => unreachable!(),
-        };
-        // The first byte of the answer is the size of the answer
-        // The minimum possible answer is 2 bytes [size, header]
-        if (answer_size < header_length) || ((answer[0] as usize) != answer_size) {
-            return Err(Error::UsbProtocolError(
-                "Inline header from radio malformed, try to update your radio".to_string(),
-            ));
-        }
-
-        let ack_received = answer[1] & IN_HEADER_ACK_RECEIVED != 0;
-
-        // Decode RSSI value if available
-        let rssi_dbm = if self.inline_mode == InlineMode::OnWithRssi && ack_received {
-            Some(-(answer[IN_HEADER_RSSI] as i16))
-        } else {
-            None
-        };
+        command.extend_from_slice(data);
 
-        // Decode answer, at this point we are sure that answer[0] is >= 2
-        let payload_length = (answer[0] as usize) - header_length;
-        if let Some(ack_data) = ack_data {
-            let copy_len = payload_length.min(ack_data.len());
-            ack_data[0..copy_len]
-                .copy_from_slice(&answer[header_length..(header_length + copy_len)]);
-        }
+        self.bulk_out(&command, Duration::from_secs(1))?;
 
-        Ok(Ack {
-            received: ack_received,
-            power_detector: answer[1] & IN_HEADER_POWER_DETECTOR != 0,
-            retry: ((answer[1] & IN_HEADER_RETRY_MASK) >> IN_HEADER_RETRY_SHIFT) as usize,
-            length: payload_length,
-            rssi_dbm,
-        })
+        Ok(())
     }
 }
 
+// Run the blocking `f` on the thread pool of the `blocking` crate and wait for
+// its result asynchronously. Returns Error::Timeout if `f` has not returned
+// after `timeout`, `f` is then left to finish in the background and its result
+// is dropped. The pool works under any executor, tokio and async-std included.
+#[cfg(feature = "async")]
+async fn spawn_blocking_timeout<T, F>(timeout: Duration, f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    // The timer is waited on the pool as well so that no executor timer is
+    // needed. It returns early once `done_tx` is dropped.
+    let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+    let work = blocking::unblock(f);
+    let timer = blocking::unblock(move || {
+        let _ = done_rx.recv_timeout(timeout);
+    });
+
+    let result = futures_lite::future::or(work, async {
+        timer.await;
+        Err(Error::Timeout)
+    })
+    .await;
+    drop(done_tx);
+    result
+}
+
 /// # Async implementations
 ///
 /// Async wrappers for blocking operations (open, serial listing) and async
@@ -1022,7 +3412,8 @@ impl Crazyradio {
 ///
 /// The open/serial functions are implemented by spawning a thread and passing
 /// the result back through a channel. This keeps the library
-/// executor-independent.
+/// executor-independent: they can be used with tokio, async-std or any other
+/// executor.
 #[cfg(feature = "async")]
 #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
 impl Crazyradio {
@@ -1030,16 +3421,30 @@ impl Crazyradio {
     pub async fn open_first_async() -> Result<Self> {
         let (tx, rx) = flume::bounded(0);
 
-        std::thread::spawn(move || tx.send(Self::open_first()));
+        std::thread::spawn(move || {
+            let _ = tx.send(Self::open_first());
+        });
 
         rx.recv_async().await.unwrap()
     }
 
+    /// Async vesion of [Crazyradio::open_first()] giving up after `timeout`
+    ///
+    /// Returns `Error::Timeout` if the radio is not opened after `timeout`,
+    /// for example if the USB enumeration hangs on a wedged USB stack. The
+    /// blocking open then keeps running in a background thread and the radio
+    /// is closed if it finally opens.
+    pub async fn open_first_async_timeout(timeout: Duration) -> Result<Self> {
+        spawn_blocking_timeout(timeout, Self::open_first).await
+    }
+
     /// Async vesion of [Crazyradio::open_nth()]
     pub async fn open_nth_async(nth: usize) -> Result<Self> {
         let (tx, rx) = flume::bounded(0);
 
-        std::thread::spawn(move || tx.send(Self::open_nth(nth)));
+        std::thread::spawn(move || {
+            let _ = tx.send(Self::open_nth(nth));
+        });
 
         rx.recv_async().await.unwrap()
     }
@@ -1050,7 +3455,9 @@ impl Crazyradio {
 
         let (tx, rx) = flume::bounded(0);
 
-        std::thread::spawn(move || tx.send(Self::open_by_serial(&serial)));
+        std::thread::spawn(move || {
+            let _ = tx.send(Self::open_by_serial(&serial));
+        });
 
         rx.recv_async().await.unwrap()
     }
@@ -1059,7 +3466,9 @@ impl Crazyradio {
     pub async fn list_serials_async() -> Result<Vec<String>> {
         let (tx, rx) = flume::bounded(0);
 
-        std::thread::spawn(move || tx.send(Self::list_serials()));
+        std::thread::spawn(move || {
+            let _ = tx.send(Self::list_serials());
+        });
 
         rx.recv_async().await.unwrap()
     }
@@ -1100,6 +3509,23 @@ pub enum Error {
     /// Sniffer session has been closed
     #[error("Sniffer session closed")]
     SnifferSessionClosed,
+    /// Operation cancelled by the caller
+    #[error("Operation cancelled")]
+    Cancelled,
+    /// Not allowed to access the Crazyradio USB device
+    #[error("Permission denied to access the Crazyradio, on Linux make sure the udev rules are installed")]
+    PermissionDenied,
+    /// No driver usable to access the Crazyradio USB device
+    #[error("Crazyradio driver missing, on Windows install the WinUSB driver with Zadig")]
+    DriverMissing,
+    /// Deadline reached before the operation completed
+    #[error("Timeout")]
+    Timeout,
+    /// The Crazyradio USB interface is used by a kernel driver
+    #[error(
+        "Crazyradio interface used by a kernel driver, see OpenOptions::detach_kernel_driver()"
+    )]
+    KernelDriverActive,
 }
 
 impl From<rusb::Error> for Error {
@@ -1108,6 +3534,104 @@ impl From<rusb::Error> for Error {
     }
 }
 
+/// Continious carrier mode of a Crazyradio, see [Crazyradio::continuous_carrier()]
+///
+/// Disables the carrier when dropped. Use [CarrierGuard::exit()] to get the
+/// error if disabling it fails.
+pub struct CarrierGuard<'a, C: rusb::UsbContext = rusb::GlobalContext> {
+    radio: &'a mut Crazyradio<C>,
+    disabled: bool,
+}
+
+impl<C: rusb::UsbContext> CarrierGuard<'_, C> {
+    /// Disable the continious carrier
+    pub fn exit(mut self) -> Result<()> {
+        self.disable()
+    }
+
+    fn disable(&mut self) -> Result<()> {
+        self.disabled = true;
+        self.radio.set_cont_carrier(false)
+    }
+}
+
+impl<C: rusb::UsbContext> Drop for CarrierGuard<'_, C> {
+    fn drop(&mut self) {
+        if !self.disabled {
+            let _ = self.disable();
+        }
+    }
+}
+
+/// Something packets can be sent through, a [Crazyradio] or a `SharedCrazyradio`
+///
+/// This allows libraries to be generic over exclusive and shared radios by
+/// accepting `impl PacketTransport`.
+pub trait PacketTransport {
+    /// Send `payload` to `address` on `channel` and wait for the ack.
+    ///
+    /// Returns the ack and its payload.
+    fn send_packet(
+        &mut self,
+        channel: Channel,
+        address: [u8; 5],
+        payload: &[u8],
+    ) -> Result<(Ack, Vec<u8>)>;
+}
+
+impl<C: rusb::UsbContext> PacketTransport for Crazyradio<C> {
+    fn send_packet(
+        &mut self,
+        channel: Channel,
+        address: [u8; 5],
+        payload: &[u8],
+    ) -> Result<(Ack, Vec<u8>)> {
+        self.set_channel(channel)?;
+        self.set_address(&address)?;
+        self.set_ack_enable(true)?;
+
+        let (ack, ack_payload) = self.send_packet_borrowed(payload)?;
+        Ok((ack, ack_payload.to_vec()))
+    }
+}
+
+/// Broadcast mode of a Crazyradio, see [Crazyradio::enter_broadcast_mode()]
+///
+/// Restores the previous address and ack enable settings when dropped. Use
+/// [BroadcastGuard::exit()] to get the error if restoring them fails.
+pub struct BroadcastGuard<'a, C: rusb::UsbContext = rusb::GlobalContext> {
+    radio: &'a mut Crazyradio<C>,
+    previous_address: [u8; 5],
+    previous_ack_enable: bool,
+    restored: bool,
+}
+
+impl<C: rusb::UsbContext> BroadcastGuard<'_, C> {
+    /// Send a broadcast packet, see [Crazyradio::send_packet_no_ack()]
+    pub fn send_packet_no_ack(&mut self, data: &[u8]) -> Result<()> {
+        self.radio.send_packet_no_ack(data)
+    }
+
+    /// Leave broadcast mode, restoring the previous address and ack enable settings
+    pub fn exit(mut self) -> Result<()> {
+        self.restore()
+    }
+
+    fn restore(&mut self) -> Result<()> {
+        self.restored = true;
+        self.radio.set_address(&self.previous_address)?;
+        self.radio.set_ack_enable(self.previous_ack_enable)
+    }
+}
+
+impl<C: rusb::UsbContext> Drop for BroadcastGuard<'_, C> {
+    fn drop(&mut self) {
+        if !self.restored {
+            let _ = self.restore();
+        }
+    }
+}
+
 /// Ack status of a sent packet
 ///
 /// This struct contains information gathered by the radio about the transaction and the received ack packet (if any).
@@ -1127,22 +3651,293 @@ pub struct Ack {
     pub rssi_dbm: Option<i16>,
 }
 
-/// A packet received in sniffer mode
-#[derive(Debug, Clone)]
-pub struct SnifferPacket {
-    /// RSSI in dBm (negative, e.g. -60 means -60 dBm)
-    pub rssi_dbm: i16,
-    /// Pipe index the packet was received on (0 or 1)
-    pub pipe: u8,
-    /// Timestamp in microseconds (wraps every ~71 minutes)
-    pub timestamp_us: u32,
-    /// Length of the payload
-    pub length: usize,
+/// One line summary of the ack, for example `ack(received=true, retries=2, rssi=-60dBm, len=4)`
+///
+/// The RSSI is displayed as `rssi=none` when not available.
+impl std::fmt::Display for Ack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ack(received={}, retries={}, ",
+            self.received, self.retry
+        )?;
+        match self.rssi_dbm {
+            Some(rssi) => write!(f, "rssi={}dBm", rssi)?,
+            None => write!(f, "rssi=none")?,
+        }
+        write!(f, ", len={})", self.length)
+    }
 }
 
-/// Radio channel
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-#[cfg_attr(feature = "serde_support", derive(Serialize))]
+/// Status byte of the radio answer to a sent packet
+///
+/// The status is decoded into the fields of [Ack]. Its bits are:
+///  - bit 0: [AckStatus::RECEIVED], an ack has been received
+///  - bit 1: [AckStatus::POWER_DETECTOR], value of the nRF24 power detector
+///  - bits 7-4: number of retries, see [AckStatus::retry()]
+///
+/// Example:
+/// ```
+/// use crazyradio::AckStatus;
+///
+/// let status = AckStatus::from_bits(0x31);
+/// assert!(status.contains(AckStatus::RECEIVED));
+/// assert!(!status.contains(AckStatus::POWER_DETECTOR));
+/// assert_eq!(status.retry(), 3);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct AckStatus(u8);
+
+impl AckStatus {
+    /// An ack has been received
+    pub const RECEIVED: AckStatus = AckStatus(0x01);
+    /// The nRF24 power detector measured a signal stronger than -64dBm
+    pub const POWER_DETECTOR: AckStatus = AckStatus(0x02);
+
+    const RETRY_MASK: u8 = 0xf0;
+    const RETRY_SHIFT: u8 = 4;
+
+    /// Status from the status byte sent by the radio
+    pub fn from_bits(bits: u8) -> Self {
+        AckStatus(bits)
+    }
+
+    /// Status byte
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Return true if all the bits of `flags` are set
+    pub fn contains(self, flags: AckStatus) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    /// Return true if an ack has been received
+    pub fn received(self) -> bool {
+        self.contains(Self::RECEIVED)
+    }
+
+    /// Return the value of the power detector
+    pub fn power_detector(self) -> bool {
+        self.contains(Self::POWER_DETECTOR)
+    }
+
+    /// Return the number of retries, from 0 to 15
+    pub fn retry(self) -> usize {
+        ((self.0 & Self::RETRY_MASK) >> Self::RETRY_SHIFT) as usize
+    }
+
+    // Ack with this status and a payload of `length` bytes
+    fn to_ack(self, length: usize, rssi_dbm: Option<i16>) -> Ack {
+        Ack {
+            received: self.received(),
+            power_detector: self.power_detector(),
+            retry: self.retry(),
+            length,
+            rssi_dbm,
+        }
+    }
+}
+
+/// Channel found by [Crazyradio::scan_channels_detailed()]
+#[derive(Debug, Clone)]
+pub struct ScanHit {
+    /// Channel that acked
+    pub channel: Channel,
+    /// Ack received on the channel
+    pub ack: Ack,
+    /// Payload of the ack
+    pub payload: Vec<u8>,
+}
+
+/// Identity of a radio read without claiming it, see [Crazyradio::inspect()]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RadioInfo {
+    /// Serial number
+    pub serial: String,
+    /// Firmware version, as reported in the USB device descriptor
+    pub firmware_version: rusb::Version,
+}
+
+/// Result of a firmware-side scan, see [Crazyradio::scan_channels_firmware()]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirmwareScan {
+    /// Channels that acked, in increasing order
+    pub channels: Vec<Channel>,
+    /// True if the result buffer was full: more channels might have acked
+    pub truncated: bool,
+}
+
+/// Change between two scans, see [Crazyradio::scan_watch()]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanDiff {
+    /// Channels acking that did not ack in the previous scan
+    pub added: Vec<Channel>,
+    /// Channels that acked in the previous scan and do not ack anymore
+    pub removed: Vec<Channel>,
+}
+
+/// Round-trip time of sent packets, see [Crazyradio::calibrate_latency()]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// Shortest round-trip time
+    pub min: Duration,
+    /// Average round-trip time
+    pub avg: Duration,
+    /// Longest round-trip time
+    pub max: Duration,
+}
+
+/// RSSI statistics over a window of samples, see [Crazyradio::rssi_monitor()]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RssiStats {
+    /// Weakest RSSI in dBm
+    pub min: i16,
+    /// Average RSSI in dBm
+    pub avg: f32,
+    /// Strongest RSSI in dBm
+    pub max: i16,
+    /// Number of samples in the window, lower than the window size at start
+    pub samples: usize,
+}
+
+/// Step of a [SelfTestReport]
+#[derive(Debug, Clone)]
+pub struct SelfTestStep {
+    /// Name of the step, see [Crazyradio::self_test()]
+    pub name: &'static str,
+    /// Error of the step, `None` if it passed
+    pub error: Option<Error>,
+}
+
+impl SelfTestStep {
+    /// Return true if the step passed
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Result of [Crazyradio::self_test()]
+#[derive(Debug, Clone)]
+pub struct SelfTestReport {
+    /// Steps run, in order
+    pub steps: Vec<SelfTestStep>,
+}
+
+impl SelfTestReport {
+    /// Return true if all the steps passed
+    pub fn passed(&self) -> bool {
+        self.steps.iter().all(SelfTestStep::passed)
+    }
+}
+
+/// Limits the rate of an operation, see [Crazyradio::send_packet_throttled()]
+///
+/// Operations are spaced by at least `1 / max_per_second` seconds. The
+/// limiter does not accumulate unused time: after a pause, the next operation
+/// is allowed immediately but the following ones are spaced again.
+///
+/// Example:
+/// ```
+/// use crazyradio::RateLimiter;
+///
+/// let mut limiter = RateLimiter::new();
+/// for _ in 0..3 {
+///     limiter.wait(1000);
+///     // At most 1000 operations per second
+/// }
+/// ```
+#[derive(Debug, Default, Copy, Clone)]
+pub struct RateLimiter {
+    last: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// Create a limiter allowing the first operation immediately
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserve the next operation at `now`, returns the time to wait before doing it
+    ///
+    /// The operation is accounted as done after the returned time. A
+    /// `max_per_second` of 0 means no limit.
+    pub fn reserve(&mut self, now: Instant, max_per_second: u32) -> Duration {
+        if max_per_second == 0 {
+            self.last = Some(now);
+            return Duration::ZERO;
+        }
+
+        let interval = Duration::from_secs(1) / max_per_second;
+        match self.last.map(|last| last + interval) {
+            Some(next) if next > now => {
+                self.last = Some(next);
+                next - now
+            }
+            _ => {
+                self.last = Some(now);
+                Duration::ZERO
+            }
+        }
+    }
+
+    /// Sleep until the next operation is allowed, see [RateLimiter::reserve()]
+    pub fn wait(&mut self, max_per_second: u32) {
+        let delay = self.reserve(Instant::now(), max_per_second);
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+/// USB transfer statistics of a Crazyradio, see [Crazyradio::stats()]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct UsbStats {
+    /// Number of bulk OUT transfers
+    pub bulk_writes: u64,
+    /// Number of bulk IN transfers
+    pub bulk_reads: u64,
+    /// Number of control transfers
+    pub control_transfers: u64,
+    /// Number of transfers, of any kind, that timed out
+    pub timeouts: u64,
+}
+
+enum UsbTransfer {
+    BulkWrite,
+    BulkRead,
+    Control,
+}
+
+impl UsbStats {
+    fn record<T>(&mut self, transfer: UsbTransfer, result: &rusb::Result<T>) {
+        match transfer {
+            UsbTransfer::BulkWrite => self.bulk_writes += 1,
+            UsbTransfer::BulkRead => self.bulk_reads += 1,
+            UsbTransfer::Control => self.control_transfers += 1,
+        }
+        if matches!(result, Err(rusb::Error::Timeout)) {
+            self.timeouts += 1;
+        }
+    }
+}
+
+/// A packet received in sniffer mode
+#[derive(Debug, Clone)]
+pub struct SnifferPacket {
+    /// RSSI in dBm (negative, e.g. -60 means -60 dBm)
+    pub rssi_dbm: i16,
+    /// Pipe index the packet was received on (0 or 1)
+    pub pipe: u8,
+    /// Timestamp in microseconds (wraps every ~71 minutes)
+    pub timestamp_us: u32,
+    /// Length of the payload
+    pub length: usize,
+}
+
+/// Radio channel
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde_support", derive(Serialize))]
 pub struct Channel(u8);
 
 #[cfg(feature = "serde_support")]
@@ -1169,6 +3964,31 @@ impl Channel {
             Err(Error::InvalidArgument)
         }
     }
+
+    /// Iterate over the channels from `start` to `stop` included
+    ///
+    /// The iterator is empty if `start` is greater than `stop`.
+    pub fn range(start: Channel, stop: Channel) -> impl Iterator<Item = Channel> {
+        (start.0..=stop.0).map(Channel)
+    }
+
+    /// Return the next channel, or `None` if this is channel 125
+    pub fn next(&self) -> Option<Channel> {
+        self.offset(1)
+    }
+
+    /// Return the previous channel, or `None` if this is channel 0
+    pub fn prev(&self) -> Option<Channel> {
+        self.offset(-1)
+    }
+
+    /// Return the channel `delta` channels away from this one
+    ///
+    /// Returns `None` if the resulting channel is out of the 0-125 range.
+    pub fn offset(&self, delta: i16) -> Option<Channel> {
+        let channel = u8::try_from((self.0 as i16).checked_add(delta)?).ok()?;
+        Channel::from_number(channel).ok()
+    }
 }
 
 impl From<Channel> for u8 {
@@ -1178,7 +3998,7 @@ impl From<Channel> for u8 {
 }
 
 /// Radio datarate
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Datarate {
     /// 250 kbps
     Dr250K = 0,
@@ -1189,6 +4009,9 @@ pub enum Datarate {
 }
 
 /// Radio power
+///
+/// Power levels are ordered by output power in dBm.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Power {
     /// -18 dBm
     Pm18dBm = 0,
@@ -1200,11 +4023,224 @@ pub enum Power {
     P0dBm = 3,
 }
 
+impl Power {
+    /// All the power levels, from the lowest to the highest
+    pub fn all() -> [Power; 4] {
+        [Power::Pm18dBm, Power::Pm12dBm, Power::Pm6dBm, Power::P0dBm]
+    }
+
+    /// Lowest power level, -18 dBm
+    pub fn min() -> Power {
+        Power::Pm18dBm
+    }
+
+    /// Highest power level, 0 dBm
+    pub fn max() -> Power {
+        Power::P0dBm
+    }
+}
+
+/// Time the radio waits for an ack before retrying to send a packet
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AutoRetryDelay {
+    /// Fixed delay, see [Crazyradio::set_ard_time()]
+    Time(Duration),
+    /// Delay long enough to receive an ack payload of this many bytes, see
+    /// [Crazyradio::set_ard_bytes()]
+    Bytes(u8),
+}
+
+/// Radio configuration
+///
+/// Groups the settings of the radio so that they can be applied at once with
+/// [Crazyradio::reset_to()]. The default value is the configuration set by
+/// [Crazyradio::reset()].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RadioConfig {
+    /// Radio channel
+    pub channel: Channel,
+    /// Radio datarate
+    pub datarate: Datarate,
+    /// Radio address
+    pub address: [u8; 5],
+    /// Transmit power
+    pub power: Power,
+    /// Number of retries when no ack is received, see [Crazyradio::set_arc()]
+    pub arc: usize,
+    /// Time to wait for an ack
+    pub ard: AutoRetryDelay,
+    /// Wait for an ack when sending packets
+    pub ack_enable: bool,
+    /// Continuous carrier mode
+    pub cont_carrier: bool,
+}
+
+impl Default for RadioConfig {
+    fn default() -> Self {
+        RadioConfig {
+            channel: Channel(2),
+            datarate: Datarate::Dr2M,
+            address: [0xe7; 5],
+            power: Power::P0dBm,
+            arc: 3,
+            ard: AutoRetryDelay::Bytes(32),
+            ack_enable: true,
+            cont_carrier: false,
+        }
+    }
+}
+
+impl RadioConfig {
+    /// Parse a Crazyflie link URI like `radio://0/42/2M/E7E7E7E742`
+    ///
+    /// The channel, datarate (`250K`, `1M` or `2M`) and address are taken from
+    /// the URI, the address defaults to `E7E7E7E7E7` if omitted. The other
+    /// settings have their default value. The radio index is checked but
+    /// not part of the configuration. Apply the configuration with
+    /// [Crazyradio::reset_to()].
+    ///
+    /// Returns `Error::InvalidArgument` if the URI is malformed.
+    pub fn from_uri(uri: &str) -> Result<RadioConfig> {
+        let path = uri.strip_prefix("radio://").ok_or(Error::InvalidArgument)?;
+        let fields: Vec<&str> = path.split('/').collect();
+        let (index, channel, datarate, address) = match fields[..] {
+            [index, channel, datarate] => (index, channel, datarate, None),
+            [index, channel, datarate, address] => (index, channel, datarate, Some(address)),
+            _ => return Err(Error::InvalidArgument),
+        };
+
+        index.parse::<usize>().map_err(|_| Error::InvalidArgument)?;
+        let channel = channel.parse().map_err(|_| Error::InvalidArgument)?;
+        let datarate = parse_datarate(datarate)?;
+        let address = match address {
+            Some(address) => parse_address(address)?,
+            None => [0xe7; 5],
+        };
+
+        Ok(RadioConfig {
+            channel: Channel::from_number(channel)?,
+            datarate,
+            address,
+            ..Default::default()
+        })
+    }
+}
+
+// Parse a datarate written as in link URIs: 250K, 1M or 2M
+fn parse_datarate(datarate: &str) -> Result<Datarate> {
+    match datarate {
+        "250K" => Ok(Datarate::Dr250K),
+        "1M" => Ok(Datarate::Dr1M),
+        "2M" => Ok(Datarate::Dr2M),
+        _ => Err(Error::InvalidArgument),
+    }
+}
+
+// Open options and radio configuration given by the CRAZYRADIO_* variables
+// read with `var`, see Crazyradio::open_from_env()
+fn env_config<F>(var: F) -> Result<(OpenOptions, RadioConfig)>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut options = OpenOptions::new();
+    if let Some(serial) = var("CRAZYRADIO_SERIAL") {
+        options.serial(&serial);
+    }
+
+    let mut config = RadioConfig::default();
+    if let Some(channel) = var("CRAZYRADIO_CHANNEL") {
+        let channel = channel.parse().map_err(|_| Error::InvalidArgument)?;
+        config.channel = Channel::from_number(channel)?;
+    }
+    if let Some(datarate) = var("CRAZYRADIO_DATARATE") {
+        config.datarate = parse_datarate(&datarate)?;
+    }
+    if let Some(address) = var("CRAZYRADIO_ADDRESS") {
+        config.address = parse_address(&address)?;
+    }
+
+    Ok((options, config))
+}
+
+// Parse a 5 bytes address written as 10 hexadecimal digits
+fn parse_address(address: &str) -> Result<[u8; 5]> {
+    if address.len() != 10 || !address.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::InvalidArgument);
+    }
+
+    let mut bytes = [0u8; 5];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&address[2 * i..2 * i + 2], 16)
+            .map_err(|_| Error::InvalidArgument)?;
+    }
+    Ok(bytes)
+}
+
+// A single setting to send to the radio
+#[derive(Debug, PartialEq)]
+enum ConfigChange {
+    Datarate(Datarate),
+    Channel(Channel),
+    ContCarrier(bool),
+    Address([u8; 5]),
+    Power(Power),
+    Arc(usize),
+    Ard(AutoRetryDelay),
+    AckEnable(bool),
+}
+
+// List the settings to send to go from `current` to `target`, in the order
+// they are applied by reset(). All settings are listed if `current` is unknown.
+fn config_changes(current: Option<&RadioConfig>, target: &RadioConfig) -> Vec<ConfigChange> {
+    let mut changes = vec![];
+
+    if current.map(|c| c.datarate) != Some(target.datarate) {
+        changes.push(ConfigChange::Datarate(target.datarate));
+    }
+    if current.map(|c| c.channel) != Some(target.channel) {
+        changes.push(ConfigChange::Channel(target.channel));
+    }
+    if current.map(|c| c.cont_carrier) != Some(target.cont_carrier) {
+        changes.push(ConfigChange::ContCarrier(target.cont_carrier));
+    }
+    if current.map(|c| c.address) != Some(target.address) {
+        changes.push(ConfigChange::Address(target.address));
+    }
+    if current.map(|c| c.power) != Some(target.power) {
+        changes.push(ConfigChange::Power(target.power));
+    }
+    if current.map(|c| c.arc) != Some(target.arc) {
+        changes.push(ConfigChange::Arc(target.arc));
+    }
+    if current.map(|c| c.ard) != Some(target.ard) {
+        changes.push(ConfigChange::Ard(target.ard));
+    }
+    if current.map(|c| c.ack_enable) != Some(target.ack_enable) {
+        changes.push(ConfigChange::AckEnable(target.ack_enable));
+    }
+
+    changes
+}
+
+// Changes setting the RF parameters (datarate, channel, address and power) of
+// `target`, leaving the other settings alone
+fn rf_changes(target: &RadioConfig) -> Vec<ConfigChange> {
+    config_changes(None, target)
+        .into_iter()
+        .filter(|change| {
+            matches!(
+                change,
+                ConfigChange::Datarate(_)
+                    | ConfigChange::Channel(_)
+                    | ConfigChange::Address(_)
+                    | ConfigChange::Power(_)
+            )
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    #[cfg(feature = "serde_support")]
-    use serde_json;
-
     #[cfg(feature = "serde_support")]
     use super::Channel;
 
@@ -1225,7 +4261,7 @@ mod tests {
 
         let result: Result<Channel, serde_json::Error> = serde_json::from_str(test_str);
 
-        assert!(matches!(result, Err(_)));
+        assert!(result.is_err());
     }
 
     #[test]
@@ -1239,12 +4275,1837 @@ mod tests {
     }
 
     #[test]
-    fn drain_rx_queue_reads_until_the_endpoint_is_empty() {
-        let mut responses = vec![Ok(3usize), Ok(2usize), Err(rusb::Error::Timeout)];
+    fn ack_status_bits_are_decoded() {
+        use super::AckStatus;
+
+        let status = AckStatus::from_bits(0x00);
+        assert!(!status.received() && !status.power_detector());
+        assert_eq!(status.retry(), 0);
+
+        let status = AckStatus::from_bits(0x03);
+        assert!(status.contains(AckStatus::RECEIVED));
+        assert!(status.contains(AckStatus::POWER_DETECTOR));
+        assert_eq!(status.retry(), 0);
+
+        // Bits 3-2 do not change the decoded fields
+        let status = AckStatus::from_bits(0xfd);
+        assert!(status.received() && !status.power_detector());
+        assert_eq!(status.retry(), 15);
+
+        let ack = AckStatus::from_bits(0x21).to_ack(4, Some(-60));
+        assert!(ack.received && !ack.power_detector);
+        assert_eq!((ack.retry, ack.length, ack.rssi_dbm), (2, 4, Some(-60)));
+    }
 
-        let drained = super::drain_rx_queue_with(|_| responses.remove(0));
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn raw_bulk_transfers_are_not_encoded_or_decoded() {
+        use super::InlineMode;
 
-        assert!(matches!(drained, Ok(2)));
+        let mut cr = super::Crazyradio::open_first().unwrap();
+        cr.set_inline_mode(InlineMode::Off).unwrap();
+
+        assert_eq!(cr.raw_bulk_write(&[0xff]).unwrap(), 1);
+
+        // The answer still starts with the ack status byte
+        let mut buf = [0u8; 64];
+        let length = cr.raw_bulk_read(&mut buf).unwrap();
+        let (ack, payload) = super::decode_answer(&buf[..length], InlineMode::Off).unwrap();
+        assert_eq!(ack.length, length - 1);
+        assert_eq!(payload, 1..length);
+    }
+
+    #[test]
+    fn packet_loss_is_recorded_only_once_written() {
+        use super::fake_dongle::FakeDongle;
+        use super::{Error, UsbCommand};
+
+        let dongle = FakeDongle::new(0x0500);
+        let mut cr = dongle.open().unwrap();
+
+        cr.set_packet_loss_simulation(10, 20).unwrap();
+        assert_eq!(cr.packet_loss_simulation(), (10, 20));
+
+        let result = cr.set_packet_loss_simulation(101, 0);
+        assert!(matches!(result, Err(Error::InvalidArgument)));
+
+        dongle.stall(UsbCommand::SetPacketLossSimulation);
+        assert!(cr.set_packet_loss_simulation(0, 0).is_err());
+
+        assert_eq!(cr.packet_loss_simulation(), (10, 20));
+        assert_eq!(dongle.data(UsbCommand::SetPacketLossSimulation), [[10, 20]]);
+
+        let pa = FakeDongle::new(0x0053);
+        let result = pa.open().unwrap().set_packet_loss_simulation(10, 20);
+        assert!(matches!(result, Err(Error::DongleVersionNotSupported)));
+    }
+
+    #[test]
+    fn endpoints_are_recovered_by_reselecting_the_configuration() {
+        use super::fake_dongle::FakeDongle;
+
+        let dongle = FakeDongle::new(0x0053);
+        let mut cr = dongle.open().unwrap();
+
+        cr.recover_endpoints().unwrap();
+        assert_eq!(dongle.events(), ["clear_halt 0x01", "clear_halt 0x81"]);
+
+        dongle.halt();
+        cr.recover_endpoints().unwrap();
+        assert_eq!(
+            dongle.events()[2..],
+            [
+                "clear_halt 0x01",
+                "release 0",
+                "set_configuration 1",
+                "claim 0",
+                "clear_halt 0x01",
+                "clear_halt 0x81",
+            ]
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn recovering_a_healthy_radio_keeps_it_usable() {
+        let mut cr = super::Crazyradio::open_first().unwrap();
+        let config = cr.config();
+
+        cr.recover_endpoints().unwrap();
+
+        assert_eq!(cr.config(), config);
+        cr.ping().unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn radio_is_usable_after_a_usb_reset() {
+        use super::{Crazyradio, Error};
+
+        let mut cr = Crazyradio::open_first().unwrap();
+        let serial = cr.serial().unwrap();
+
+        match cr.usb_reset() {
+            Ok(()) => cr.reset().unwrap(),
+            // The radio re-enumerated and has to be opened again
+            Err(Error::NotFound) => {
+                drop(cr);
+                cr = Crazyradio::open_by_serial(&serial).unwrap();
+            }
+            Err(e) => panic!("USB reset failed: {}", e),
+        }
+
+        cr.ping().unwrap();
+    }
+
+    #[test]
+    fn rssi_requires_firmware_5_3() {
+        use super::firmware_supports_rssi;
+        use rusb::Version;
+
+        assert!(!firmware_supports_rssi(Version::from_bcd(0x0050)));
+        assert!(!firmware_supports_rssi(Version::from_bcd(0x0520)));
+        assert!(firmware_supports_rssi(Version::from_bcd(0x0530)));
+        assert!(firmware_supports_rssi(Version::from_bcd(0x0600)));
+    }
+
+    #[test]
+    fn firmware_scan_is_supported_since_crazyradio_pa_0_5() {
+        use super::firmware_supports_scan;
+        use rusb::Version;
+
+        assert!(!firmware_supports_scan(Version::from_bcd(0x0040)));
+        assert!(firmware_supports_scan(Version::from_bcd(0x0050)));
+        assert!(firmware_supports_scan(Version::from_bcd(0x0099)));
+        assert!(firmware_supports_scan(Version::from_bcd(0x0500)));
+    }
+
+    #[test]
+    fn reset_to_the_current_config_sends_nothing() {
+        let config = super::RadioConfig::default();
+
+        assert!(super::config_changes(Some(&config), &config).is_empty());
+    }
+
+    #[test]
+    fn reset_to_sends_only_the_changed_settings() {
+        let current = super::RadioConfig::default();
+        let target = super::RadioConfig {
+            arc: 10,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            super::config_changes(Some(&current), &target),
+            vec![super::ConfigChange::Arc(10)]
+        );
+        assert_eq!(super::config_changes(None, &target).len(), 8);
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn applying_a_config_twice_sends_nothing_the_second_time() {
+        use super::{Crazyradio, InlineMode, RadioConfig};
+
+        let mut cr = Crazyradio::open_first().unwrap();
+        cr.set_inline_mode(InlineMode::Off).unwrap();
+
+        let drone_a = RadioConfig {
+            channel: super::Channel::from_number(80).unwrap(),
+            address: [0xe7, 0xe7, 0xe7, 0xe7, 0x01],
+            ..cr.config()
+        };
+        let drone_b = RadioConfig {
+            address: [0xe7, 0xe7, 0xe7, 0xe7, 0x02],
+            ..drone_a.clone()
+        };
+
+        let before = cr.stats().control_transfers;
+        assert_eq!(cr.apply_config_diff(&drone_a).unwrap(), 2);
+        assert_eq!(cr.stats().control_transfers - before, 2);
+        assert_eq!(cr.apply_config_diff(&drone_a).unwrap(), 0);
+        assert_eq!(cr.apply_config_diff(&drone_b).unwrap(), 1);
+
+        // The address is sent with each packet in inline mode
+        if cr.set_inline_mode(InlineMode::On).is_ok() {
+            assert_eq!(cr.apply_config_diff(&drone_a).unwrap(), 0);
+            assert_eq!(cr.config(), drone_a);
+        }
+    }
+
+    #[test]
+    fn applying_a_config_twice_sends_no_transfer_the_second_time() {
+        use super::fake_dongle::FakeDongle;
+        use super::{Channel, InlineMode, RadioConfig};
+
+        let dongle = FakeDongle::new(0x0530);
+        let mut cr = dongle.open().unwrap();
+        cr.set_inline_mode(InlineMode::Off).unwrap();
+
+        let drone_a = RadioConfig {
+            channel: Channel(80),
+            address: [0xe7, 0xe7, 0xe7, 0xe7, 0x01],
+            ..cr.config()
+        };
+        let drone_b = RadioConfig {
+            address: [0xe7, 0xe7, 0xe7, 0xe7, 0x02],
+            ..drone_a.clone()
+        };
+
+        let before = dongle.request_count();
+        assert_eq!(cr.apply_config_diff(&drone_a).unwrap(), 2);
+        assert_eq!(dongle.request_count() - before, 2);
+        assert_eq!(cr.apply_config_diff(&drone_a).unwrap(), 0);
+        assert_eq!(cr.apply_config_diff(&drone_b).unwrap(), 1);
+        assert_eq!(dongle.request_count() - before, 3);
+        assert_eq!(dongle.address(), drone_b.address);
+
+        // The address is sent with each packet in inline mode
+        cr.set_inline_mode(InlineMode::On).unwrap();
+        let before = dongle.request_count();
+        assert_eq!(cr.apply_config_diff(&drone_a).unwrap(), 0);
+        assert_eq!(dongle.request_count(), before);
+        assert_eq!(cr.config(), drone_a);
+    }
+
+    #[test]
+    fn scan_stops_when_cancelled() {
+        use super::fake_dongle::FakeDongle;
+        use super::{Channel, Datarate, Error};
+        use std::sync::atomic::AtomicBool;
+
+        let dongle = FakeDongle::new(0x0053);
+        dongle.add_device(Channel(5), Datarate::Dr2M, &[]);
+        let mut cr = dongle.open().unwrap();
+
+        let cancelled = AtomicBool::new(true);
+        let result = cr.scan_channels_cancellable(Channel(0), Channel(10), &[0xff], &cancelled);
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert!(dongle.packets().is_empty());
+
+        let running = AtomicBool::new(false);
+        let found = cr
+            .scan_channels_cancellable(Channel(0), Channel(10), &[0xff], &running)
+            .unwrap();
+        assert_eq!(found, [Channel(5)]);
+        assert_eq!(dongle.packets().len(), 11);
+        assert_eq!(dongle.channel(), Channel(10));
+    }
+
+    #[test]
+    fn repeated_send_sends_count_packets() {
+        use super::fake_dongle::FakeDongle;
+
+        let dongle = FakeDongle::new(0x0053);
+        let mut cr = dongle.open().unwrap();
+        cr.set_ack_enable(false).unwrap();
+
+        cr.send_packet_no_ack_repeated(&[0xff], 3, std::time::Duration::ZERO)
+            .unwrap();
+
+        assert_eq!(dongle.packets(), [[0xff], [0xff], [0xff]]);
+    }
+
+    #[test]
+    fn hopping_send_stops_at_the_first_acking_channel() {
+        use super::fake_dongle::FakeDongle;
+        use super::{Channel, Datarate, Error};
+
+        let dongle = FakeDongle::new(0x0053);
+        dongle.add_device(Channel(30), Datarate::Dr2M, &[]);
+        let mut cr = dongle.open().unwrap();
+        let channels = [Channel(10), Channel(20), Channel(30), Channel(40)];
+
+        let mut ack_data = [0u8; 32];
+        let (channel, ack) = cr
+            .send_packet_hopping(&channels, &[0xff], &mut ack_data)
+            .unwrap();
+
+        assert_eq!(channel, Channel(30));
+        assert!(ack.received);
+        assert_eq!(dongle.packets().len(), 3);
+
+        let result = cr.send_packet_hopping(&[], &[0xff], &mut ack_data);
+        assert!(matches!(result, Err(Error::InvalidArgument)));
+    }
+
+    #[test]
+    fn adaptive_send_steps_down_to_an_acking_datarate() {
+        use super::fake_dongle::FakeDongle;
+        use super::{Channel, Datarate, UsbCommand};
+
+        let dongle = FakeDongle::new(0x0053);
+        dongle.add_device(Channel(2), Datarate::Dr1M, &[]);
+        let mut cr = dongle.open().unwrap();
+
+        let mut ack_data = [0u8; 32];
+        let acks: Vec<bool> = (0..5)
+            .map(|_| {
+                cr.send_packet_adaptive(&[0xff], &mut ack_data)
+                    .unwrap()
+                    .received
+            })
+            .collect();
+
+        // The datarate is lowered after more than 3 sends without ack
+        assert_eq!(acks, [false, false, false, true, true]);
+        assert_eq!(dongle.packets().len(), 6);
+        assert_eq!(
+            dongle.values(UsbCommand::SetDataRate).last(),
+            Some(&(Datarate::Dr1M as u16))
+        );
+        assert_eq!(cr.config().datarate, Datarate::Dr1M);
+    }
+
+    #[test]
+    fn noise_floor_is_the_fraction_of_tripped_samples() {
+        use super::fake_dongle::FakeDongle;
+        use super::{Channel, Error};
+
+        let dongle = FakeDongle::new(0x0053);
+        dongle.add_noise(Channel(10));
+        let mut cr = dongle.open().unwrap();
+
+        assert_eq!(cr.noise_floor(Channel(10), 4).unwrap(), 1.0);
+        assert_eq!(cr.noise_floor(Channel(20), 4).unwrap(), 0.0);
+        assert_eq!(dongle.channel(), Channel(2));
+
+        let result = cr.noise_floor(Channel(10), 0);
+        assert!(matches!(result, Err(Error::InvalidArgument)));
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn noise_floor_restores_the_channel() {
+        use super::Channel;
+
+        let mut cr = super::Crazyradio::open_first().unwrap();
+        cr.set_channel(Channel(10)).unwrap();
+
+        let noise = cr.noise_floor(Channel(80), 20).unwrap();
+
+        assert!((0.0..=1.0).contains(&noise));
+        assert_eq!(cr.config().channel, Channel(10));
+    }
+
+    #[test]
+    fn usb_stats_count_each_transfer_and_timeouts() {
+        let mut stats = super::UsbStats::default();
+
+        stats.record(super::UsbTransfer::BulkWrite, &Ok(1));
+        stats.record(super::UsbTransfer::BulkRead, &Ok(33));
+        stats.record(
+            super::UsbTransfer::BulkRead,
+            &Err::<usize, _>(rusb::Error::Timeout),
+        );
+        stats.record(
+            super::UsbTransfer::Control,
+            &Err::<usize, _>(rusb::Error::Pipe),
+        );
+
+        assert_eq!(
+            stats,
+            super::UsbStats {
+                bulk_writes: 1,
+                bulk_reads: 2,
+                control_transfers: 1,
+                timeouts: 1,
+            }
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn sending_a_packet_counts_one_write_and_one_read() {
+        let mut cr = super::Crazyradio::open_first().unwrap();
+        cr.reset_stats();
+
+        let mut ack_data = [0u8; 32];
+        cr.send_packet(&[0xff], &mut ack_data).unwrap();
+
+        let stats = cr.stats();
+        assert_eq!((stats.bulk_writes, stats.bulk_reads), (1, 1));
+    }
+
+    #[test]
+    fn ard_time_is_rounded_up_to_250us_steps() {
+        use std::time::Duration;
+
+        assert!(matches!(super::ard_time_register(Duration::ZERO), Ok(0)));
+        assert!(matches!(
+            super::ard_time_register(Duration::from_micros(500)),
+            Ok(1)
+        ));
+        assert!(matches!(
+            super::ard_time_register(Duration::from_micros(501)),
+            Ok(2)
+        ));
+        assert!(matches!(
+            super::ard_time_register(Duration::from_millis(4)),
+            Ok(15)
+        ));
+        assert!(matches!(
+            super::ard_time_register(Duration::from_micros(4001)),
+            Err(super::Error::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn auto_ard_is_longer_at_250k_than_at_2m() {
+        use super::{ack_time, ard_time_register, Datarate};
+
+        let ard_250k = ard_time_register(ack_time(Datarate::Dr250K, 32)).unwrap();
+        let ard_2m = ard_time_register(ack_time(Datarate::Dr2M, 32)).unwrap();
+
+        assert_eq!(ard_250k, 5);
+        assert_eq!(ard_2m, 1);
+    }
+
+    #[test]
+    fn address_is_restored_after_sending_to_another_one() {
+        use super::fake_dongle::FakeDongle;
+        use super::Error;
+
+        let dongle = FakeDongle::new(0x0053);
+        let mut cr = dongle.open().unwrap();
+        let address = dongle.address();
+
+        let mut ack_data = [0u8; 32];
+        cr.send_packet_to([0x42; 5], &[0xff], &mut ack_data)
+            .unwrap();
+        assert_eq!(dongle.address(), address);
+
+        let result = cr.send_packet_to([0x42; 5], &[], &mut ack_data);
+        assert!(matches!(result, Err(Error::InvalidArgument)));
+        assert_eq!(dongle.address(), address);
+    }
+
+    #[test]
+    fn ping_is_true_only_if_a_device_acks() {
+        use super::fake_dongle::FakeDongle;
+        use super::{Channel, Datarate};
+
+        let dongle = FakeDongle::new(0x0053);
+        let mut cr = dongle.open().unwrap();
+        assert!(!cr.ping().unwrap());
+
+        dongle.add_device(Channel(2), Datarate::Dr2M, &[]);
+        assert!(cr.ping().unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "testing")]
+    fn packet_transport_is_implemented_by_the_radio_and_the_fake() {
+        use super::fake_dongle::FakeDongle;
+        use super::testing::{FakeCommand, FakeRadio};
+        use super::{Channel, Datarate, PacketTransport, Result};
+
+        // Library code generic over the transport
+        fn probe(transport: &mut impl PacketTransport) -> Result<Vec<u8>> {
+            let (ack, payload) = transport.send_packet(Channel(42), [0xe7; 5], &[0xff])?;
+            assert!(ack.received);
+            Ok(payload)
+        }
+
+        let mut fake = FakeRadio::new();
+        fake.push_ack(&[0x0b]);
+        assert_eq!(probe(&mut fake).unwrap(), [0x0b]);
+        assert_eq!(fake.commands()[0], FakeCommand::SetChannel(Channel(42)));
+
+        let dongle = FakeDongle::new(0x0053);
+        dongle.add_device(Channel(42), Datarate::Dr2M, &[0x0b]);
+        let mut cr = dongle.open().unwrap();
+        assert_eq!(probe(&mut cr).unwrap(), [0x0b]);
+        assert_eq!(dongle.channel(), Channel(42));
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn send_packet_to_restores_the_address() {
+        let mut cr = super::Crazyradio::open_first().unwrap();
+        cr.set_address(&[0xe7, 0xe7, 0xe7, 0xe7, 0x01]).unwrap();
+        let previous = cr.config();
+        let mut ack_data = [0u8; 32];
+
+        cr.send_packet_to([0xff; 5], &[0xff], &mut ack_data)
+            .unwrap();
+        assert_eq!(cr.config(), previous);
+
+        // An empty packet is rejected after the address is set
+        let result = cr.send_packet_to([0xff; 5], &[], &mut ack_data);
+        assert!(matches!(result, Err(super::Error::InvalidArgument)));
+        assert_eq!(cr.config(), previous);
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn dropping_the_broadcast_guard_restores_address_and_ack() {
+        use super::{Crazyradio, Error, Result};
+
+        let mut cr = Crazyradio::open_first().unwrap();
+        cr.set_address(&[0xe7, 0xe7, 0xe7, 0xe7, 0x01]).unwrap();
+        let previous = cr.config();
+
+        {
+            let mut broadcast = cr.enter_broadcast_mode([0xff; 5]).unwrap();
+            broadcast.send_packet_no_ack(&[0xff]).unwrap();
+        }
+        assert_eq!(cr.config(), previous);
+
+        cr.enter_broadcast_mode([0xff; 5]).unwrap().exit().unwrap();
+        assert_eq!(cr.config(), previous);
+
+        // The guard is dropped when returning early with an error
+        fn broadcast_then_fail(cr: &mut Crazyradio) -> Result<()> {
+            let mut broadcast = cr.enter_broadcast_mode([0xff; 5])?;
+            broadcast.send_packet_no_ack(&[0xff])?;
+            Err(Error::Cancelled)
+        }
+        assert!(matches!(
+            broadcast_then_fail(&mut cr),
+            Err(Error::Cancelled)
+        ));
+        assert_eq!(cr.config(), previous);
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn cont_carrier_enabled_reflects_the_last_setting() {
+        let mut cr = super::Crazyradio::open_first().unwrap();
+        assert!(!cr.cont_carrier_enabled());
+
+        cr.set_cont_carrier(true).unwrap();
+        assert!(cr.cont_carrier_enabled());
+        cr.set_cont_carrier(false).unwrap();
+        assert!(!cr.cont_carrier_enabled());
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn carrier_is_disabled_when_the_guard_is_dropped_or_exited() {
+        let mut cr = super::Crazyradio::open_first().unwrap();
+
+        {
+            let _carrier = cr.continuous_carrier().unwrap();
+        }
+        assert!(!cr.cont_carrier_enabled());
+        assert!(!cr.config().cont_carrier);
+
+        let carrier = cr.continuous_carrier().unwrap();
+        carrier.exit().unwrap();
+        assert!(!cr.cont_carrier_enabled());
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn probe_restores_the_configuration() {
+        use super::{Channel, Datarate, Error};
+
+        let mut cr = super::Crazyradio::open_first().unwrap();
+        cr.set_ack_enable(false).unwrap();
+        let previous = cr.config();
+
+        cr.probe(Datarate::Dr250K, Channel(125), [0x12; 5], &[0xff])
+            .unwrap();
+        assert_eq!(cr.config(), previous);
+
+        // An empty packet is rejected after the probe configuration is set
+        let result = cr.probe(Datarate::Dr250K, Channel(125), [0x12; 5], &[]);
+        assert!(matches!(result, Err(Error::InvalidArgument)));
+        assert_eq!(cr.config(), previous);
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn retries_are_disabled_for_one_send_only() {
+        let mut cr = super::Crazyradio::open_first().unwrap();
+        cr.set_arc(3).unwrap();
+        let mut ack_data = [0u8; 32];
+
+        let ack = cr.send_packet_no_retry(&[0xff], &mut ack_data).unwrap();
+
+        assert_eq!(ack.retry, 0);
+        assert_eq!(cr.config().arc, 3);
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn broadcast_once_restores_the_unicast_settings() {
+        let mut cr = super::Crazyradio::open_first().unwrap();
+        cr.set_channel(super::Channel(80)).unwrap();
+        let previous = cr.config();
+
+        cr.broadcast_once(super::Channel(10), [0xff; 5], &[0xff])
+            .unwrap();
+
+        assert_eq!(cr.config(), previous);
+    }
+
+    #[test]
+    fn rate_limiter_spaces_operations() {
+        use std::time::{Duration, Instant};
+
+        let ms = Duration::from_millis;
+        let t0 = Instant::now();
+        let mut limiter = super::RateLimiter::new();
+
+        assert_eq!(limiter.reserve(t0, 100), Duration::ZERO);
+        assert_eq!(limiter.reserve(t0 + ms(2), 100), ms(8));
+        // The previous operation is accounted at t0 + 10ms
+        assert_eq!(limiter.reserve(t0 + ms(12), 100), ms(8));
+        assert_eq!(limiter.reserve(t0 + ms(45), 100), Duration::ZERO);
+        assert_eq!(limiter.reserve(t0 + ms(46), 0), Duration::ZERO);
+    }
+
+    #[test]
+    fn self_test_reports_each_step() {
+        use super::fake_dongle::FakeDongle;
+        use super::UsbCommand;
+
+        let dongle = FakeDongle::new(0x0053);
+        let mut cr = dongle.open().unwrap();
+
+        let report = cr.self_test().unwrap();
+        assert!(report.passed());
+        assert_eq!(report.steps.len(), 7);
+
+        dongle.stall(UsbCommand::SetContCarrier);
+        let report = cr.self_test().unwrap();
+        assert!(!report.passed());
+        let failed: Vec<_> = report
+            .steps
+            .iter()
+            .filter(|step| !step.passed())
+            .map(|step| step.name)
+            .collect();
+        assert_eq!(failed, ["continuous carrier"]);
+    }
+
+    #[test]
+    fn rssi_statistics_roll_over_the_window() {
+        let mut window = super::RssiWindow::new(3);
+        let stats: Vec<_> = [-40, -50, -60, -70]
+            .into_iter()
+            .map(|rssi| window.push(rssi))
+            .map(|s| (s.min, s.avg, s.max, s.samples))
+            .collect();
+
+        assert_eq!(
+            stats,
+            [
+                (-40, -40.0, -40, 1),
+                (-50, -45.0, -40, 2),
+                (-60, -50.0, -40, 3),
+                (-70, -60.0, -50, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn channel_arithmetic_stops_at_the_boundaries() {
+        use super::Channel;
+
+        assert_eq!(Channel(0).prev(), None);
+        assert_eq!(Channel(0).next(), Some(Channel(1)));
+        assert_eq!(Channel(125).next(), None);
+        assert_eq!(Channel(125).prev(), Some(Channel(124)));
+        assert_eq!(Channel(42).offset(10), Some(Channel(52)));
+        assert_eq!(Channel(42).offset(-42), Some(Channel(0)));
+        assert_eq!(Channel(42).offset(-43), None);
+        assert_eq!(Channel(42).offset(i16::MAX), None);
+    }
+
+    #[test]
+    fn scan_with_start_after_stop_is_an_error() {
+        use super::fake_dongle::FakeDongle;
+        use super::Channel;
+
+        let dongle = FakeDongle::new(0x0053);
+        let mut cr = dongle.open().unwrap();
+
+        let result = cr.scan_channels(Channel(42), Channel(41), &[0xff]);
+
+        assert!(matches!(result, Err(super::Error::InvalidArgument)));
+        assert!(dongle.packets().is_empty());
+    }
+
+    #[test]
+    fn full_firmware_scan_result_is_flagged_as_truncated() {
+        use super::{parse_firmware_scan, Channel};
+
+        let partial = parse_firmware_scan(&[2, 3, 4], 3, Channel(0), Channel(10)).unwrap();
+        assert_eq!(partial.channels, [Channel(2), Channel(3), Channel(4)]);
+        assert!(partial.truncated);
+
+        // A full buffer is complete if it holds all the scanned channels
+        let all = parse_firmware_scan(&[2, 3, 4], 3, Channel(2), Channel(4)).unwrap();
+        assert!(!all.truncated);
+
+        let some = parse_firmware_scan(&[2, 4], 3, Channel(0), Channel(10)).unwrap();
+        assert!(!some.truncated);
+
+        assert!(parse_firmware_scan(&[20], 3, Channel(0), Channel(10)).is_err());
+    }
+
+    #[test]
+    fn scan_watch_reports_appearing_and_disappearing_channels() {
+        use super::fake_dongle::FakeDongle;
+        use super::{Channel, Datarate};
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::Duration;
+
+        let dongle = FakeDongle::new(0x0053);
+        dongle.add_device(Channel(5), Datarate::Dr2M, &[]);
+        let mut cr = dongle.open().unwrap();
+
+        let stop = AtomicBool::new(false);
+        let mut diffs = vec![];
+        cr.scan_watch(
+            Channel(0),
+            Channel(10),
+            &[0xff],
+            Duration::ZERO,
+            |diff| {
+                diffs.push((diff.added.clone(), diff.removed.clone()));
+                // The device moves to another channel, then the watch stops
+                dongle.remove_devices();
+                if diffs.len() == 1 {
+                    dongle.add_device(Channel(8), Datarate::Dr2M, &[]);
+                } else {
+                    stop.store(true, Ordering::Relaxed);
+                }
+            },
+            &stop,
+        )
+        .unwrap();
+
+        assert_eq!(
+            diffs,
+            [
+                (vec![Channel(5)], vec![]),
+                (vec![Channel(8)], vec![Channel(5)]),
+            ]
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn blocking_work_times_out() {
+        use super::{spawn_blocking_timeout, Error};
+        use std::time::Duration;
+
+        let result = spawn_blocking_timeout(Duration::from_millis(10), || {
+            std::thread::sleep(Duration::from_secs(1));
+            Ok(())
+        })
+        .await;
+        assert!(matches!(result, Err(Error::Timeout)));
+
+        let result = spawn_blocking_timeout(Duration::from_secs(10), || Ok(42)).await;
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    #[cfg(feature = "async")]
+    #[async_std::test]
+    async fn blocking_work_times_out_under_async_std() {
+        use super::{spawn_blocking_timeout, Error};
+        use std::time::Duration;
+
+        let result = spawn_blocking_timeout(Duration::from_millis(10), || {
+            std::thread::sleep(Duration::from_secs(1));
+            Ok(())
+        })
+        .await;
+        assert!(matches!(result, Err(Error::Timeout)));
+
+        let result = spawn_blocking_timeout(Duration::from_secs(10), || Ok(42)).await;
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn blocking_work_times_out_without_a_runtime() {
+        use super::{spawn_blocking_timeout, Error};
+        use std::future::Future;
+        use std::task::{Context, Poll, Waker};
+        use std::time::Duration;
+
+        // Minimal executor polling the future until it is ready
+        fn block_on<F: Future>(future: F) -> F::Output {
+            let mut future = std::pin::pin!(future);
+            let mut cx = Context::from_waker(Waker::noop());
+            loop {
+                if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                    return output;
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        let result = block_on(spawn_blocking_timeout(Duration::from_millis(10), || {
+            std::thread::sleep(Duration::from_secs(1));
+            Ok(())
+        }));
+        assert!(matches!(result, Err(Error::Timeout)));
+
+        let result = block_on(spawn_blocking_timeout(Duration::from_secs(10), || Ok(42)));
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    #[test]
+    fn repeated_settings_are_written_once() {
+        use super::{setting_needs_write, Channel, InlineMode};
+
+        let mut current = (Channel(2), [0xe7; 5]);
+        let mut writes = 0;
+        for requested in [(Channel(42), [0xe7; 5]); 5] {
+            writes += setting_needs_write(InlineMode::Off, true, &current.0, &requested.0) as usize;
+            writes += setting_needs_write(InlineMode::Off, true, &current.1, &requested.1) as usize;
+            current = requested;
+        }
+        assert_eq!(writes, 1);
+
+        assert!(setting_needs_write(
+            InlineMode::Off,
+            false,
+            &current,
+            &current
+        ));
+        assert!(!setting_needs_write(
+            InlineMode::On,
+            false,
+            &Channel(2),
+            &Channel(42)
+        ));
+    }
+
+    #[test]
+    fn raw_answer_keeps_the_status_byte() {
+        let answer = [0x31, 0xaa, 0xbb];
+
+        let mut out = [0u8; 32];
+        assert_eq!(super::copy_raw_answer(&answer, &mut out), 3);
+        assert_eq!(out[..3], answer);
+
+        let mut short = [0u8; 2];
+        assert_eq!(super::copy_raw_answer(&answer, &mut short), 3);
+        assert_eq!(short, [0x31, 0xaa]);
+    }
+
+    #[test]
+    fn scan_until_stops_after_max_found_channels() {
+        use super::fake_dongle::FakeDongle;
+        use super::{Channel, Datarate};
+
+        let dongle = FakeDongle::new(0x0053);
+        for channel in [10, 20, 30] {
+            dongle.add_device(Channel(channel), Datarate::Dr2M, &[]);
+        }
+        let mut cr = dongle.open().unwrap();
+
+        let found = cr
+            .scan_channels_until(Channel(0), Channel(40), &[0xff], 2)
+            .unwrap();
+
+        assert_eq!(found, [Channel(10), Channel(20)]);
+        assert_eq!(dongle.packets().len(), 21);
+    }
+
+    #[test]
+    fn timeboxed_scan_stops_when_the_budget_elapses() {
+        use super::fake_dongle::FakeDongle;
+        use super::{Channel, Datarate};
+        use std::time::Duration;
+
+        let dongle = FakeDongle::new(0x0053);
+        dongle.add_device(Channel(5), Datarate::Dr2M, &[]);
+        let mut cr = dongle.open().unwrap();
+
+        let found = cr
+            .scan_channels_timeboxed(Channel(0), Channel(10), &[0xff], Duration::ZERO)
+            .unwrap();
+        assert!(found.is_empty());
+        assert!(dongle.packets().is_empty());
+
+        let found = cr
+            .scan_channels_timeboxed(Channel(0), Channel(10), &[0xff], Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(found, [Channel(5)]);
+    }
+
+    #[test]
+    fn scan_channel_list_only_probes_the_listed_channels() {
+        use super::fake_dongle::FakeDongle;
+        use super::{Channel, Datarate};
+
+        let dongle = FakeDongle::new(0x0053);
+        dongle.add_device(Channel(80), Datarate::Dr2M, &[]);
+        dongle.add_device(Channel(40), Datarate::Dr2M, &[]);
+        let mut cr = dongle.open().unwrap();
+
+        let found = cr
+            .scan_channel_list(&[Channel(80), Channel(2), Channel(40)], &[0xff])
+            .unwrap();
+
+        assert_eq!(found, [Channel(80), Channel(40)]);
+        assert_eq!(dongle.packets().len(), 3);
+    }
+
+    #[test]
+    fn channel_range_includes_both_ends() {
+        use super::Channel;
+
+        let channels: Vec<_> = Channel::range(Channel(123), Channel(125)).collect();
+
+        assert_eq!(channels, [Channel(123), Channel(124), Channel(125)]);
+        assert_eq!(Channel::range(Channel(5), Channel(4)).count(), 0);
+    }
+
+    #[test]
+    fn ack_payload_follows_the_status_byte() {
+        let answer = [0x21, 0x01, 0x02, 0x03];
+
+        let (ack, payload) = super::decode_ack(&answer).unwrap();
+
+        assert!(ack.received);
+        assert_eq!(ack.retry, 2);
+        assert_eq!(ack.length, 3);
+        assert_eq!(&answer[payload], &[0x01, 0x02, 0x03]);
+    }
+
+    #[test]
+    fn only_unacked_energy_makes_a_probe_busy() {
+        let probe = |received, power_detector| super::Ack {
+            received,
+            power_detector,
+            retry: 0,
+            length: 0,
+            rssi_dbm: None,
+        };
+
+        assert!(super::probe_is_busy(&probe(false, true)));
+        assert!(!super::probe_is_busy(&probe(true, true)));
+        assert!(!super::probe_is_busy(&probe(true, false)));
+        assert!(!super::probe_is_busy(&probe(false, false)));
+    }
+
+    #[test]
+    fn auto_select_channel_picks_the_quietest_candidate() {
+        use super::fake_dongle::FakeDongle;
+        use super::{Channel, Error};
+
+        let dongle = FakeDongle::new(0x0053);
+        dongle.add_noise(Channel(10));
+        dongle.add_noise(Channel(70));
+        let mut cr = dongle.open().unwrap();
+
+        let candidates = [Channel(10), Channel(40), Channel(70), Channel(100)];
+        assert_eq!(cr.auto_select_channel(&candidates, 3).unwrap(), Channel(40));
+        assert_eq!(dongle.channel(), Channel(40));
+
+        let result = cr.auto_select_channel(&[], 3);
+        assert!(matches!(result, Err(Error::InvalidArgument)));
+    }
+
+    #[test]
+    fn ack_display_is_a_one_line_summary() {
+        let ack = super::Ack {
+            received: true,
+            power_detector: false,
+            retry: 2,
+            length: 4,
+            rssi_dbm: Some(-60),
+        };
+        assert_eq!(
+            ack.to_string(),
+            "ack(received=true, retries=2, rssi=-60dBm, len=4)"
+        );
+
+        let no_ack = super::Ack {
+            received: false,
+            retry: 3,
+            length: 0,
+            rssi_dbm: None,
+            ..ack
+        };
+        assert_eq!(
+            no_ack.to_string(),
+            "ack(received=false, retries=3, rssi=none, len=0)"
+        );
+    }
+
+    #[test]
+    fn link_uri_matches_the_crazyflie_format() {
+        use super::{Channel, Datarate};
+
+        let address = [0xe7, 0xe7, 0xe7, 0xe7, 0x42];
+        assert_eq!(
+            super::link_uri(0, Channel(42), Datarate::Dr2M, &address),
+            "radio://0/42/2M/E7E7E7E742"
+        );
+        assert_eq!(
+            super::link_uri(1, Channel(5), Datarate::Dr250K, &address),
+            "radio://1/5/250K/E7E7E7E742"
+        );
+    }
+
+    #[test]
+    fn power_levels_are_sorted_by_dbm() {
+        use super::Power;
+
+        let all = Power::all();
+        assert!(all.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(Power::min(), Power::Pm18dBm);
+        assert_eq!(Power::max(), Power::P0dBm);
+        assert_eq!(all.iter().max(), Some(&Power::max()));
+    }
+
+    #[test]
+    fn max_packet_size_is_read_from_the_in_endpoint() {
+        let endpoints = [(0x01, 32), (0x81, 64)];
+
+        let size = super::endpoint_max_packet_size(endpoints.into_iter(), 0x81);
+        assert_eq!(size, Some(64));
+        let missing = super::endpoint_max_packet_size(endpoints.into_iter(), 0x82);
+        assert_eq!(missing, None);
+        let high_bandwidth = [(0x81, 0x1000 | 512)].into_iter();
+        assert_eq!(
+            super::endpoint_max_packet_size(high_bandwidth, 0x81),
+            Some(512)
+        );
+    }
+
+    #[test]
+    fn rx_buffer_holds_one_usb_packet_and_the_longest_answer() {
+        assert_eq!(super::rx_buffer_length(64), 64);
+        assert_eq!(super::rx_buffer_length(512), 512);
+        assert_eq!(super::rx_buffer_length(16), super::MAX_INLINE_ACK_SIZE);
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn crazyradio_bulk_in_max_packet_size_is_64_bytes() {
+        let cr = super::Crazyradio::open_first().unwrap();
+
+        assert_eq!(cr.max_packet_size().unwrap(), 64);
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn crazyradio_reports_its_usb_speed() {
+        let cr = super::Crazyradio::open_first().unwrap();
+
+        let speed = cr.usb_speed();
+        println!("Crazyradio connected at {:?} speed", speed);
+
+        // The Crazyradio is a full speed device
+        assert!(matches!(speed, super::UsbSpeed::Full));
+    }
+
+    #[test]
+    fn latency_is_measured_over_each_sample() {
+        use super::fake_dongle::FakeDongle;
+        use super::Error;
+
+        let dongle = FakeDongle::new(0x0053);
+        let mut cr = dongle.open().unwrap();
+
+        let stats = cr.calibrate_latency(3).unwrap();
+        assert!(stats.min <= stats.avg && stats.avg <= stats.max);
+        assert_eq!(dongle.packets().len(), 3);
+
+        assert!(matches!(
+            cr.calibrate_latency(0),
+            Err(Error::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn enumerate_opens_each_connected_radio() {
+        use super::Crazyradio;
+
+        let serials = Crazyradio::list_serials().unwrap();
+
+        let opened: Vec<_> = Crazyradio::enumerate()
+            .map(|cr| cr.unwrap().serial().unwrap())
+            .collect();
+
+        assert!(!opened.is_empty());
+        assert_eq!(opened, serials);
+    }
+
+    #[test]
+    fn fetched_ack_is_decoded_from_the_answer_in_each_inline_mode() {
+        use super::{copy_ack_payload, decode_answer, InlineMode};
+
+        // Acked after 2 retries with the payload [0xaa, 0xbb]
+        let answers: [(InlineMode, &[u8]); 2] = [
+            (InlineMode::Off, &[0x21, 0xaa, 0xbb]),
+            (InlineMode::On, &[4, 0x21, 0xaa, 0xbb]),
+        ];
+
+        for (inline_mode, answer) in answers {
+            let (ack, payload) = decode_answer(answer, inline_mode).unwrap();
+            let mut ack_data = [0u8; 32];
+            let copied = copy_ack_payload(&answer[payload], &mut ack_data);
+
+            assert!(ack.received);
+            assert_eq!(ack.retry, 2);
+            assert_eq!(ack.length, 2);
+            assert_eq!(&ack_data[..copied], [0xaa, 0xbb]);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn submit_then_fetch_acks_like_send_packet() {
+        // No device answers on this channel and address
+        let mut cr = super::Crazyradio::open_first().unwrap();
+        cr.set_channel(super::Channel::from_number(125).unwrap())
+            .unwrap();
+        cr.set_address(&[0x12, 0x34, 0x56, 0x78, 0x9a]).unwrap();
+        let mut ack_data = [0u8; 32];
+
+        let sent = cr.send_packet(&[0xff], &mut ack_data).unwrap();
+        cr.submit_packet(&[0xff]).unwrap();
+        let fetched = cr.fetch_ack(&mut ack_data).unwrap();
+
+        assert_eq!(
+            (sent.received, sent.length, sent.retry),
+            (fetched.received, fetched.length, fetched.retry)
+        );
+    }
+
+    #[test]
+    fn scan_packet_is_at_most_32_bytes() {
+        assert!(super::check_payload_length(&[]).is_ok());
+        assert!(super::check_payload_length(&[0xff; 32]).is_ok());
+        assert!(matches!(
+            super::check_payload_length(&[0xff; 33]),
+            Err(super::Error::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn passed_deadline_is_a_timeout() {
+        use std::time::{Duration, Instant};
+
+        let now = Instant::now();
+        let deadline = now + Duration::from_millis(10);
+
+        assert_eq!(
+            super::time_until(deadline, now).unwrap(),
+            Duration::from_millis(10)
+        );
+        assert!(matches!(
+            super::time_until(deadline, deadline),
+            Err(super::Error::Timeout)
+        ));
+        assert!(matches!(
+            super::time_until(now, deadline),
+            Err(super::Error::Timeout)
+        ));
+    }
+
+    #[test]
+    fn open_errors_point_to_the_system_setup() {
+        use super::{open_error, Error};
+
+        assert!(matches!(
+            open_error(rusb::Error::Access),
+            Error::PermissionDenied
+        ));
+        assert!(matches!(
+            open_error(rusb::Error::NotSupported),
+            Error::DriverMissing
+        ));
+        assert!(matches!(
+            open_error(rusb::Error::Busy),
+            Error::UsbError(rusb::Error::Busy)
+        ));
+        assert!(Error::PermissionDenied.to_string().contains("udev"));
+    }
+
+    #[test]
+    fn parallel_scan_splits_the_channels_between_radios() {
+        use super::fake_dongle::FakeDongle;
+        use super::{Channel, Crazyradio, Datarate};
+
+        assert_eq!(
+            super::split_channels(Channel(0), Channel(124), 2),
+            [(Channel(0), Channel(62)), (Channel(63), Channel(124))]
+        );
+
+        let a = FakeDongle::new(0x0053);
+        a.add_device(Channel(5), Datarate::Dr2M, &[]);
+        a.add_device(Channel(70), Datarate::Dr2M, &[]);
+        let b = FakeDongle::new(0x0053);
+        b.add_device(Channel(100), Datarate::Dr2M, &[]);
+        let mut radios = [a.open().unwrap(), b.open().unwrap()];
+
+        let found =
+            Crazyradio::parallel_scan(&mut radios, Channel(0), Channel(124), &[0xff]).unwrap();
+
+        // Each radio only scans its half of the range
+        assert_eq!(found, [Channel(5), Channel(100)]);
+        assert_eq!(a.packets().len(), 63);
+        assert_eq!(b.packets().len(), 62);
+    }
+
+    #[test]
+    fn empty_packets_are_rejected() {
+        assert!(matches!(
+            super::check_packet_data(&[]),
+            Err(super::Error::InvalidArgument)
+        ));
+        assert!(super::check_packet_data(&[0xff]).is_ok());
+    }
+
+    #[test]
+    fn interface_setting_must_exist() {
+        let settings = [(0, 0), (1, 0), (1, 1)];
+
+        assert!(super::check_interface_setting(&settings, 0, 0).is_ok());
+        assert!(super::check_interface_setting(&settings, 1, 1).is_ok());
+        assert!(super::check_interface_setting(&settings, 0, 1).is_err());
+        assert!(super::check_interface_setting(&settings, 2, 0).is_err());
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn only_existing_alternate_settings_are_selected() {
+        let mut cr = super::OpenOptions::new()
+            .alternate_setting(0)
+            .open()
+            .unwrap();
+
+        // The Crazyradio radio interface has a single alternate setting
+        assert!(matches!(
+            cr.set_alternate_setting(1),
+            Err(super::Error::InvalidArgument)
+        ));
+        cr.set_alternate_setting(0).unwrap();
+
+        cr.ping().unwrap();
+    }
+
+    #[test]
+    fn scan_duration_covers_all_the_packets() {
+        use super::{Channel, Crazyradio};
+        use std::time::Duration;
+
+        let full_band = Crazyradio::estimated_scan_duration(
+            Channel(0),
+            Channel(125),
+            2,
+            3,
+            Duration::from_millis(1),
+        );
+        assert_eq!(full_band, Duration::from_millis(756));
+
+        let empty = Crazyradio::estimated_scan_duration(
+            Channel(5),
+            Channel(4),
+            2,
+            3,
+            Duration::from_millis(1),
+        );
+        assert_eq!(empty, Duration::ZERO);
+
+        let endless = Crazyradio::estimated_scan_duration(
+            Channel(0),
+            Channel(125),
+            usize::MAX,
+            1,
+            Duration::from_nanos(1),
+        );
+        assert_eq!(endless, Duration::MAX);
+
+        let endless =
+            Crazyradio::estimated_scan_duration(Channel(0), Channel(0), 2, 1, Duration::MAX);
+        assert_eq!(endless, Duration::MAX);
+    }
+
+    #[test]
+    fn radio_config_from_uri_round_trips() {
+        use super::{link_uri, Channel, Datarate, RadioConfig};
+
+        let uri = "radio://0/42/2M/E7E7E7E742";
+        let config = RadioConfig::from_uri(uri).unwrap();
+
+        assert_eq!(config.channel, Channel(42));
+        assert_eq!(config.datarate, Datarate::Dr2M);
+        assert_eq!(config.address, [0xe7, 0xe7, 0xe7, 0xe7, 0x42]);
+        assert_eq!(
+            link_uri(0, config.channel, config.datarate, &config.address),
+            uri
+        );
+
+        let default_address = RadioConfig::from_uri("radio://1/80/250K").unwrap();
+        assert_eq!(default_address.address, [0xe7; 5]);
+
+        for malformed in [
+            "usb://0",
+            "radio://0/126/2M",
+            "radio://0/42/3M",
+            "radio://0/42/2M/E7E7",
+            "radio://x/42/2M",
+        ] {
+            assert!(RadioConfig::from_uri(malformed).is_err(), "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn weak_acks_are_filtered_out() {
+        let ack = |received, rssi_dbm| super::Ack {
+            received,
+            power_detector: false,
+            retry: 0,
+            length: 0,
+            rssi_dbm,
+        };
+
+        assert!(super::ack_meets_rssi(&ack(true, Some(-60)), -80));
+        assert!(!super::ack_meets_rssi(&ack(true, Some(-90)), -80));
+        assert!(super::ack_meets_rssi(&ack(true, None), -80));
+        assert!(!super::ack_meets_rssi(&ack(false, None), -80));
+    }
+
+    #[test]
+    fn rf_reset_only_sets_the_rf_parameters() {
+        use super::{Channel, ConfigChange, Datarate, Power, RadioConfig};
+
+        assert_eq!(
+            super::rf_changes(&RadioConfig::default()),
+            [
+                ConfigChange::Datarate(Datarate::Dr2M),
+                ConfigChange::Channel(Channel(2)),
+                ConfigChange::Address([0xe7; 5]),
+                ConfigChange::Power(Power::P0dBm),
+            ]
+        );
+    }
+
+    #[test]
+    fn scan_hits_carry_the_probe_answer() {
+        use super::fake_dongle::FakeDongle;
+        use super::{Channel, Datarate};
+
+        let dongle = FakeDongle::new(0x0053);
+        dongle.add_device(Channel(42), Datarate::Dr2M, &[0x0b, 0x01]);
+        let mut cr = dongle.open().unwrap();
+
+        let hits = cr
+            .scan_channels_detailed(Channel(40), Channel(44), &[0xff])
+            .unwrap();
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].channel, Channel(42));
+        assert!(hits[0].ack.received);
+        assert_eq!(hits[0].payload, [0x0b, 0x01]);
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn detailed_scan_probes_each_channel_of_the_range() {
+        use super::Channel;
+
+        let mut cr = super::Crazyradio::open_first().unwrap();
+        let before = cr.stats();
+
+        let hits = cr
+            .scan_channels_detailed(Channel(10), Channel(14), &[0xff])
+            .unwrap();
+
+        assert!(hits
+            .iter()
+            .all(|hit| hit.ack.received && (10..=14).contains(&u8::from(hit.channel))));
+        assert_eq!(cr.stats().bulk_writes - before.bulk_writes, 5);
+        assert_eq!(cr.config().channel, Channel(14));
+    }
+
+    #[test]
+    fn bulk_endpoints_must_exist_with_the_right_direction() {
+        let endpoints = [0x01, 0x81, 0x02, 0x82];
+
+        assert!(super::check_bulk_endpoints(&endpoints, 0x01, 0x81).is_ok());
+        assert!(super::check_bulk_endpoints(&endpoints, 0x02, 0x82).is_ok());
+        assert!(super::check_bulk_endpoints(&endpoints, 0x81, 0x01).is_err());
+        assert!(super::check_bulk_endpoints(&endpoints, 0x03, 0x81).is_err());
+        assert!(super::check_bulk_endpoints(&endpoints, 0x01, 0x83).is_err());
+    }
+
+    #[test]
+    fn sniff_enters_sniffer_mode_before_listening() {
+        use super::fake_dongle::FakeDongle;
+        use super::{Channel, UsbCommand};
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let dongle = FakeDongle::new(0x0053);
+        let mut cr = dongle.open().unwrap();
+        // [length, rssi, pipe, timestamp, payload]
+        dongle.push_answer(&[9, 60, 0, 0x10, 0x00, 0x00, 0x00, 0x01, 0x02]);
+
+        let stop = AtomicBool::new(false);
+        let mut packets = vec![];
+        cr.sniff(
+            Channel(80),
+            [0xe7; 5],
+            |packet| {
+                packets.push(packet.to_vec());
+                stop.store(true, Ordering::Relaxed);
+            },
+            &stop,
+        )
+        .unwrap();
+
+        assert_eq!(packets, [[0x01, 0x02]]);
+        assert!(dongle.values(UsbCommand::SetRadioMode).ends_with(&[1, 0]));
+        assert_eq!(dongle.channel(), Channel(80));
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn sniff_leaves_sniffer_mode_when_stopped() {
+        use std::sync::atomic::AtomicBool;
+
+        let mut cr = super::Crazyradio::open_first().unwrap();
+
+        cr.sniff(
+            super::Channel(80),
+            [0xe7; 5],
+            |_| (),
+            &AtomicBool::new(true),
+        )
+        .unwrap();
+
+        assert_eq!(cr.config().channel, super::Channel(80));
+        cr.ping().unwrap();
+    }
+
+    #[test]
+    fn strict_ack_check_rejects_no_ack_packets_with_acks_enabled() {
+        use super::check_ack_mode;
+
+        assert!(check_ack_mode(true, true, false).is_err());
+        assert!(check_ack_mode(true, false, true).is_err());
+        assert!(check_ack_mode(true, true, true).is_ok());
+        assert!(check_ack_mode(true, false, false).is_ok());
+        assert!(check_ack_mode(false, true, false).is_ok());
+    }
+
+    #[test]
+    fn scan_waits_for_the_settle_time_after_setting_the_channel() {
+        use super::fake_dongle::FakeDongle;
+        use super::Channel;
+        use std::time::{Duration, Instant};
+
+        let dongle = FakeDongle::new(0x0053);
+        let mut cr = dongle.open().unwrap();
+        cr.set_scan_settle_time(Duration::from_millis(5));
+
+        let start = Instant::now();
+        cr.scan_channels(Channel(0), Channel(3), &[0xff]).unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "packet data must be at most 32 bytes, got 33")]
+    fn oversized_packet_data_panics_in_debug_builds() {
+        super::debug_assert_payload_length(&[0xff; 33]);
+    }
+
+    #[test]
+    fn retransmit_window_multiplies_count_and_delay() {
+        use super::{retransmit_window, AutoRetryDelay, Datarate};
+        use std::time::Duration;
+
+        let ms = Duration::from_millis;
+        let delay = AutoRetryDelay::Time(ms(1));
+        assert_eq!(retransmit_window(3, delay, Datarate::Dr2M), ms(3));
+        assert_eq!(retransmit_window(0, delay, Datarate::Dr2M), ms(0));
+
+        // Rounded up to the next 250us step like in the radio
+        let delay = AutoRetryDelay::Time(Duration::from_micros(600));
+        assert_eq!(
+            retransmit_window(2, delay, Datarate::Dr2M),
+            ms(1) + ms(1) / 2
+        );
+
+        // 32 bytes ack: 294.5us at 2M and 1.446ms at 250K
+        let delay = AutoRetryDelay::Bytes(32);
+        assert_eq!(retransmit_window(4, delay, Datarate::Dr2M), ms(2));
+        assert_eq!(retransmit_window(4, delay, Datarate::Dr250K), ms(6));
+    }
+
+    #[test]
+    fn claim_fails_with_a_typed_error_when_a_kernel_driver_is_bound() {
+        use super::{claim_error, Error};
+
+        assert!(matches!(
+            claim_error(rusb::Error::Busy, true),
+            Error::KernelDriverActive
+        ));
+        assert!(matches!(
+            claim_error(rusb::Error::Busy, false),
+            Error::UsbError(rusb::Error::Busy)
+        ));
+        assert!(matches!(
+            claim_error(rusb::Error::Access, true),
+            Error::PermissionDenied
+        ));
+        assert!(super::OpenOptions::new().detach_kernel_driver);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn kernel_driver_is_detached_on_open() {
+        let mut cr = super::OpenOptions::new()
+            .detach_kernel_driver(true)
+            .open()
+            .unwrap();
+
+        // The driver state is shared by all the handles of the device
+        let handle = rusb::open_device_with_vid_pid(0x1915, 0x7777).unwrap();
+        assert!(!handle.kernel_driver_active(0).unwrap());
+        cr.ping().unwrap();
+    }
+
+    #[test]
+    fn retry_average_converges_to_the_retry_count() {
+        let ack = |retry| super::Ack {
+            received: true,
+            power_detector: false,
+            retry,
+            length: 0,
+            rssi_dbm: None,
+        };
+
+        let mut average = super::update_retry_average(None, &ack(0));
+        assert_eq!(average, 0.0);
+        average = super::update_retry_average(Some(average), &ack(10));
+        assert!((average - 1.0).abs() < 1e-6);
+
+        for _ in 0..100 {
+            average = super::update_retry_average(Some(average), &ack(3));
+        }
+        assert!((average - 3.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn retry_callback_receives_the_retry_counts() {
+        let retries = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let mut tracker = super::RetryTracker::default();
+        let received = retries.clone();
+        tracker.callback = Some(std::sync::Mutex::new(Box::new(move |retry| {
+            received.lock().unwrap().push(retry)
+        })));
+
+        for retry in [0, 2, 0, 5] {
+            tracker.record(&super::Ack {
+                received: retry < 5,
+                power_detector: false,
+                retry,
+                length: 0,
+                rssi_dbm: None,
+            });
+        }
+
+        assert_eq!(*retries.lock().unwrap(), [0, 2, 0, 5]);
+    }
+
+    #[test]
+    fn open_from_env_reads_the_selection_and_config() {
+        use super::{env_config, Channel, Datarate};
+        use std::collections::HashMap;
+
+        let mut env = HashMap::from([
+            ("CRAZYRADIO_SERIAL", "FD61E54B7A"),
+            ("CRAZYRADIO_CHANNEL", "42"),
+            ("CRAZYRADIO_DATARATE", "250K"),
+        ]);
+        let (options, config) = env_config(|name| env.get(name).map(|v| v.to_string())).unwrap();
+        assert_eq!(options.serial.as_deref(), Some("FD61E54B7A"));
+        assert_eq!(config.channel, Channel(42));
+        assert_eq!(config.datarate, Datarate::Dr250K);
+        assert_eq!(config.address, [0xe7; 5]);
+
+        env.insert("CRAZYRADIO_CHANNEL", "126");
+        assert!(env_config(|name| env.get(name).map(|v| v.to_string())).is_err());
+
+        let (options, config) = env_config(|_| None).unwrap();
+        assert_eq!(options.serial, None);
+        assert_eq!(config, super::RadioConfig::default());
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn radio_in_use_is_not_available() {
+        use super::Crazyradio;
+
+        let cr = Crazyradio::open_first().unwrap();
+        let serial = cr.serial().unwrap();
+
+        // Depending on the platform, the radio in use cannot be opened at all
+        let radios = Crazyradio::list_with_status().unwrap();
+        assert!(
+            radios.contains(&(serial.clone(), false)) || radios.contains(&(String::new(), false))
+        );
+
+        drop(cr);
+        let radios = Crazyradio::list_with_status().unwrap();
+        assert!(radios.contains(&(serial, true)));
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn inspect_does_not_disturb_a_claimed_radio() {
+        use super::{Crazyradio, OpenOptions};
+
+        let mut cr = Crazyradio::open_first().unwrap();
+        let serial = cr.serial().unwrap();
+        let config = cr.config();
+
+        let info = Crazyradio::inspect(OpenOptions::new().serial(&serial)).unwrap();
+        assert_eq!(info.serial, serial);
+
+        assert_eq!(cr.config(), config);
+        cr.ping().unwrap();
+    }
+
+    #[test]
+    fn crazyradio_is_identified_by_vendor_and_product_id() {
+        assert!(super::is_crazyradio(0x1915, 0x7777));
+        assert!(!super::is_crazyradio(0x1915, 0x0101));
+        assert!(!super::is_crazyradio(0x0483, 0x7777));
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn radio_is_opened_from_an_enumerated_device() {
+        use super::{Crazyradio, Error};
+
+        let mut opened = 0;
+        for device in rusb::devices().unwrap().iter() {
+            let desc = device.device_descriptor().unwrap();
+            match Crazyradio::from_device(device) {
+                Ok(mut cr) => {
+                    cr.ping().unwrap();
+                    opened += 1;
+                }
+                Err(Error::InvalidArgument) => {
+                    assert!(!super::is_crazyradio(desc.vendor_id(), desc.product_id()))
+                }
+                Err(e) => panic!("Cannot open the radio: {}", e),
+            }
+        }
+
+        assert!(opened > 0);
+    }
+
+    #[test]
+    fn usb_constants_match_the_crazyradio_protocol() {
+        use rusb::{
+            constants::LIBUSB_ENDPOINT_IN, request_type, Direction, Recipient, RequestType,
+        };
+
+        assert_eq!(
+            super::REQUEST_TYPE_VENDOR_OUT,
+            request_type(Direction::Out, RequestType::Vendor, Recipient::Device)
+        );
+        assert_eq!(
+            super::REQUEST_TYPE_VENDOR_IN,
+            request_type(Direction::In, RequestType::Vendor, Recipient::Device)
+        );
+        // Both bulk endpoints are number 1, IN is told apart by its direction bit
+        assert_eq!(super::ENDPOINT_BULK_OUT & LIBUSB_ENDPOINT_IN, 0);
+        assert_eq!(
+            super::ENDPOINT_BULK_IN,
+            super::ENDPOINT_BULK_OUT | LIBUSB_ENDPOINT_IN
+        );
+    }
+
+    #[test]
+    fn oversized_bulk_read_is_rejected() {
+        // The answer is read in a buffer of one USB packet, 64 bytes
+        assert!(super::rx_buffer_length(64) > super::MAX_ACK_SIZE);
+        assert_eq!(super::check_read_size(33, super::MAX_ACK_SIZE).unwrap(), 33);
+        assert!(matches!(
+            super::check_read_size(34, super::MAX_ACK_SIZE),
+            Err(super::Error::UsbProtocolError(_))
+        ));
+    }
+
+    #[test]
+    fn status_only_ack_leaves_the_caller_buffer_untouched() {
+        let mut received = [0x55u8; 33];
+        received[0] = 0x01;
+        let (ack, payload) = super::decode_ack(&received[..1]).unwrap();
+
+        let mut ack_data = [0xaa; 32];
+        let copied = super::copy_ack_payload(&received[payload], &mut ack_data);
+
+        assert!(ack.received);
+        assert_eq!(ack.length, 0);
+        assert_eq!(copied, 0);
+        assert_eq!(ack_data, [0xaa; 32]);
+    }
+
+    #[test]
+    fn inline_ack_payload_follows_the_header() {
+        use super::InlineMode;
+
+        let answer = [5, 0x01, 60, 0xaa, 0xbb];
+
+        let (ack, payload) = super::decode_inline_ack(&answer, InlineMode::OnWithRssi).unwrap();
+        assert_eq!(ack.rssi_dbm, Some(-60));
+        assert_eq!(ack.length, 2);
+        assert_eq!(&answer[payload], &[0xaa, 0xbb]);
+
+        let (ack, payload) = super::decode_inline_ack(&answer, InlineMode::On).unwrap();
+        assert_eq!(ack.rssi_dbm, None);
+        assert_eq!(&answer[payload], &[60, 0xaa, 0xbb]);
+
+        assert!(super::decode_inline_ack(&answer[..4], InlineMode::On).is_err());
+    }
+
+    #[test]
+    fn open_options_reset_the_radio_by_default() {
+        let mut options = super::OpenOptions::new();
+        assert!(options.reset_on_open);
+
+        options.nth(1).reset_on_open(false);
+        assert_eq!(options.nth, Some(1));
+        assert!(!options.reset_on_open);
+    }
+
+    #[test]
+    fn radio_is_only_reset_on_open_if_requested() {
+        use super::fake_dongle::FakeDongle;
+        use super::OpenOptions;
+
+        let dongle = FakeDongle::new(0x0053);
+        dongle
+            .open_with(OpenOptions::new().reset_on_open(false))
+            .unwrap();
+        assert_eq!(dongle.request_count(), 0);
+
+        dongle.open().unwrap();
+        assert!(dongle.request_count() > 0);
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn radio_opened_without_reset_is_left_untouched() {
+        let cr = super::OpenOptions::new()
+            .reset_on_open(false)
+            .open()
+            .unwrap();
+
+        assert_eq!(cr.stats(), super::UsbStats::default());
+    }
+
+    #[test]
+    fn open_with_an_explicit_context() {
+        use super::{Crazyradio, Error, OpenOptions};
+
+        // libusb cannot be initialized on systems without USB support
+        let Ok(context) = rusb::Context::new() else {
+            return;
+        };
+        match Crazyradio::open_with_context(&context, &OpenOptions::new()) {
+            Ok(cr) => assert!(cr.serial().is_ok()),
+            // No radio connected, or not accessible from the test environment
+            Err(e) => assert!(
+                matches!(
+                    e,
+                    Error::NotFound | Error::PermissionDenied | Error::DriverMissing
+                ),
+                "{}",
+                e
+            ),
+        }
+    }
+
+    #[test]
+    fn drain_rx_queue_reads_until_the_endpoint_is_empty() {
+        let mut responses = vec![Ok(3usize), Ok(2usize), Err(rusb::Error::Timeout)];
+
+        let drained = super::drain_rx_queue_with(|_| responses.remove(0));
+
+        assert!(matches!(drained, Ok(2)));
+    }
+
+    #[test]
+    fn pending_ack_is_drained_before_the_setting_changes() {
+        use super::fake_dongle::FakeDongle;
+        use super::Channel;
+
+        let dongle = FakeDongle::new(0x0053);
+        let mut cr = dongle.open().unwrap();
+
+        cr.submit_packet(&[0xff]).unwrap();
+        assert_eq!(cr.set_channel_flushed(Channel(10)).unwrap(), 1);
+        assert_eq!(cr.set_channel_flushed(Channel(20)).unwrap(), 0);
+        assert_eq!(dongle.channel(), Channel(20));
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn submitted_packet_answer_is_discarded_by_a_flushed_setting() {
+        let mut cr = super::Crazyradio::open_first().unwrap();
+
+        cr.submit_packet(&[0xff]).unwrap();
+        assert_eq!(cr.set_channel_flushed(super::Channel(10)).unwrap(), 1);
+
+        cr.submit_packet(&[0xff]).unwrap();
+        assert_eq!(cr.set_address_flushed(&[0xe7; 5]).unwrap(), 1);
+
+        assert_eq!(cr.set_channel_flushed(super::Channel(20)).unwrap(), 0);
     }
 
     #[test]
@@ -1,9 +1,12 @@
 #![cfg(feature = "shared_radio")]
 #![cfg_attr(docsrs, doc(cfg(feature = "shared_radio")))]
 
-use crate::Result;
-use crate::{Ack, Channel, Crazyradio};
+use crate::{Ack, Channel, Crazyradio, Datarate, PacketTransport, Power};
+use crate::{Error, Result};
 use flume::{bounded, unbounded, Receiver, Sender, WeakSender};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Multi-user threaded Crazyradio
 ///
@@ -16,6 +19,20 @@ use flume::{bounded, unbounded, Receiver, Sender, WeakSender};
 /// thread is stopped and the radio object is dropped which
 /// closes the USB connection.
 ///
+/// Each request gets its own reply channel, on which the radio thread sends
+/// the answer to this request only: answers cannot be mixed up between
+/// requests, even when several requests are pending on the same handle, and
+/// dropping an async request before it completes does not affect the
+/// following requests. The radio thread executes the requests of all the
+/// handles one at a time, in the order they are received. To issue requests
+/// from several threads or tasks, give each of them its own clone.
+///
+/// Every request carries its channel and address, but they are only sent to
+/// the radio when they change: the radio thread relies on the settings cache
+/// of [Crazyradio], see [Crazyradio::set_cache_settings()]. Streaming packets
+/// to one device therefore costs no extra control transfer per packet. This
+/// is transparent to the users of the shared radio.
+///
 /// Usage example:
 /// ``` no_run
 /// let radio = crazyradio::Crazyradio::open_first().unwrap();
@@ -37,12 +54,6 @@ use flume::{bounded, unbounded, Receiver, Sender, WeakSender};
 ///
 pub struct SharedCrazyradio {
     radio_command: Sender<RadioCommand>,
-    send_packet_res_send: Sender<Result<SendPacketResult>>,
-    send_packet_res: Receiver<Result<SendPacketResult>>,
-    send_packet_no_ack_res_send: Sender<Result<()>>,
-    send_packet_no_ack_res: Receiver<Result<()>>,
-    scan_res_send: Sender<Result<ScanResult>>,
-    scan_res: Receiver<Result<ScanResult>>,
 }
 
 impl SharedCrazyradio {
@@ -57,22 +68,52 @@ impl SharedCrazyradio {
         let (radio_command, radio_command_recv) = unbounded();
 
         std::thread::spawn(move || {
-            radio_loop(radio, radio_command_recv);
+            radio_loop(radio, radio_command_recv, None);
         });
 
-        let (send_packet_res_send, send_packet_res) = bounded(1);
-        let (send_packet_no_ack_res_send, send_packet_no_ack_res) = bounded(1);
-        let (scan_res_send, scan_res) = bounded(1);
-
-        SharedCrazyradio {
-            radio_command,
-            send_packet_res_send,
-            send_packet_res,
-            send_packet_no_ack_res_send,
-            send_packet_no_ack_res,
-            scan_res_send,
-            scan_res,
-        }
+        Self::from_command_sender(radio_command)
+    }
+
+    /// Create a shared crazyradio that reopens the radio if it is disconnected.
+    ///
+    /// Same as [SharedCrazyradio::new()] but when a request fails because the
+    /// radio has been disconnected (`rusb::Error::NoDevice`), for example if
+    /// the dongle is briefly unplugged, the radio thread tries to open the
+    /// radio with the same serial number again. It retries after 100ms, 200ms,
+    /// 400ms, 800ms and 1.6s. The radio thread is busy during the whole
+    /// recovery.
+    ///
+    /// If the radio is reopened, the radio configuration (datarate, power,
+    /// ...) is restored and the failed request is run again on the new radio.
+    /// Otherwise, the request fails with the original error and recovery is
+    /// attempted again on the next request.
+    pub fn new_with_recovery(radio: Crazyradio) -> Result<Self> {
+        let recovery = Recovery {
+            serial: radio.serial()?,
+            backoff: (0..5).map(|i| Duration::from_millis(100 << i)).collect(),
+        };
+        let (radio_command, radio_command_recv) = unbounded();
+
+        std::thread::spawn(move || {
+            radio_loop(radio, radio_command_recv, Some(recovery));
+        });
+
+        Ok(Self::from_command_sender(radio_command))
+    }
+
+    // Create a handle on the radio thread listening to `radio_command`
+    fn from_command_sender(radio_command: Sender<RadioCommand>) -> Self {
+        SharedCrazyradio { radio_command }
+    }
+
+    // Send the command built by `command` to the radio thread and wait for
+    // the answer on a reply channel created for this request. The channel is
+    // bounded to one element, the radio thread never blocks sending a reply.
+    fn request<T>(&self, command: impl FnOnce(Sender<Result<T>>) -> RadioCommand) -> Result<T> {
+        let (client, result) = bounded(1);
+        self.radio_command.send(command(client)).unwrap();
+
+        result.recv().unwrap()
     }
 
     /// Scan channels between start and stop for a specified address and payload.
@@ -88,17 +129,32 @@ impl SharedCrazyradio {
         address: [u8; 5],
         payload: Vec<u8>,
     ) -> Result<Vec<Channel>> {
-        self.radio_command
-            .send(RadioCommand::Scan {
-                client: self.scan_res_send.clone(),
-                start,
-                stop,
-                address,
-                payload,
-            })
-            .unwrap();
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.scan_cancellable(start, stop, address, payload, cancel)
+    }
 
-        let result = self.scan_res.recv().unwrap()?;
+    /// Cancellable version of [SharedCrazyradio::scan()].
+    ///
+    /// The radio thread checks `cancel` before probing each channel. Setting
+    /// it from any thread stops the scan which then returns `Error::Cancelled`
+    /// and releases the radio for the other users. See
+    /// [Crazyradio::scan_channels_cancellable()].
+    pub fn scan_cancellable(
+        &self,
+        start: Channel,
+        stop: Channel,
+        address: [u8; 5],
+        payload: Vec<u8>,
+        cancel: Arc<AtomicBool>,
+    ) -> Result<Vec<Channel>> {
+        let result = self.request(|client| RadioCommand::Scan {
+            client,
+            start,
+            stop,
+            address,
+            payload,
+            cancel,
+        })?;
 
         Ok(result.found)
     }
@@ -116,27 +172,48 @@ impl SharedCrazyradio {
         address: [u8; 5],
         payload: Vec<u8>,
     ) -> Result<(Ack, Vec<u8>)> {
+        let result = self.request(|client| RadioCommand::SendPacket {
+            client,
+            channel,
+            address,
+            payload,
+        })?;
+
+        Ok(result.into_ack())
+    }
+
+    /// Queue a packet to a `channel`, `address` containing `payload` without waiting for the ack.
+    ///
+    /// Non-blocking version of [SharedCrazyradio::send_packet()]: the returned
+    /// [PendingPacket] is polled for the result, which suits applications
+    /// polling from an event loop. Async applications should use
+    /// `send_packet_async()` instead.
+    ///
+    /// Each pending packet has its own reply channel, so several packets can
+    /// be pending on the same handle. They are sent in order by the radio
+    /// thread.
+    ///
+    /// Returns `Error::NotFound` if the radio thread has stopped.
+    pub fn try_send_packet(
+        &self,
+        channel: Channel,
+        address: [u8; 5],
+        payload: Vec<u8>,
+    ) -> Result<PendingPacket> {
+        let (client, result) = bounded(1);
         self.radio_command
             .send(RadioCommand::SendPacket {
-                client: self.send_packet_res_send.clone(),
+                client,
                 channel,
                 address,
                 payload,
             })
-            .unwrap();
-
-        let result = self.send_packet_res.recv().unwrap()?;
+            .map_err(|_| Error::NotFound)?;
 
-        Ok((
-            Ack {
-                received: result.acked,
-                length: result.payload.len(),
-                power_detector: result.power_detector,
-                retry: result.retry,
-                rssi_dbm: result.rssi_dbm,
-            },
-            result.payload,
-        ))
+        Ok(PendingPacket {
+            result,
+            done: false,
+        })
     }
 
     /// Send a packet to a `channel`, `address` containing `payload` without caring about an Ack.
@@ -149,15 +226,108 @@ impl SharedCrazyradio {
         address: [u8; 5],
         payload: Vec<u8>,
     ) -> Result<()> {
+        self.request(|client| RadioCommand::SendPacketNoAck {
+            client,
+            channel,
+            address,
+            payload,
+        })
+    }
+
+    /// Send the same packet `count` times, spaced by `interval`, without caring about an Ack.
+    ///
+    /// The radio is taken for the whole burst. See
+    /// [Crazyradio::send_packet_no_ack_repeated()].
+    pub fn send_packet_no_ack_repeated(
+        &mut self,
+        channel: Channel,
+        address: [u8; 5],
+        payload: Vec<u8>,
+        count: usize,
+        interval: Duration,
+    ) -> Result<()> {
+        self.request(|client| RadioCommand::SendPacketNoAckRepeated {
+            client,
+            channel,
+            address,
+            payload,
+            count,
+            interval,
+        })
+    }
+
+    /// Set the radio datarate, see [Crazyradio::set_datarate()]
+    ///
+    /// The radio settings are shared by all the users of the radio. The
+    /// channel and address are not settings of the shared radio since they are
+    /// passed with each packet.
+    pub fn set_datarate(&mut self, datarate: Datarate) -> Result<()> {
+        self.configure(RadioSetting::Datarate(datarate))
+    }
+
+    /// Set the radio transmit power, see [Crazyradio::set_power()]
+    pub fn set_power(&mut self, power: Power) -> Result<()> {
+        self.configure(RadioSetting::Power(power))
+    }
+
+    /// Set the number of retry, see [Crazyradio::set_arc()]
+    pub fn set_arc(&mut self, arc: usize) -> Result<()> {
+        self.configure(RadioSetting::Arc(arc))
+    }
+
+    /// Set the time to wait for an ack, see [Crazyradio::set_ard_time()]
+    pub fn set_ard_time(&mut self, delay: Duration) -> Result<()> {
+        self.configure(RadioSetting::ArdTime(delay))
+    }
+
+    /// Set the ack payload length to wait for, see [Crazyradio::set_ard_bytes()]
+    pub fn set_ard_bytes(&mut self, nbytes: u8) -> Result<()> {
+        self.configure(RadioSetting::ArdBytes(nbytes))
+    }
+
+    /// Run `f` with exclusive access to the radio, in the radio thread
+    ///
+    /// This allows to use the [Crazyradio] functions not exposed by
+    /// [SharedCrazyradio]. The closure runs between two requests of the other
+    /// handles, and its result is returned. Settings changed by the closure
+    /// also apply to the following requests of all the handles.
+    ///
+    /// The closure is run once: if the radio has been created with
+    /// [SharedCrazyradio::new_with_recovery()], it is not run again after
+    /// reopening the radio. Returns `Error::NotFound` if the radio thread has
+    /// stopped.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # fn main() -> Result<(), crazyradio::Error> {
+    /// let radio = crazyradio::Crazyradio::open_first()?;
+    /// let radio = crazyradio::SharedCrazyradio::new(radio);
+    ///
+    /// let serial = radio.with_radio(|cr| cr.serial())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_radio<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Crazyradio) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let (client, result) = bounded(1);
         self.radio_command
-            .send(RadioCommand::SendPacketNoAck {
-                client: self.send_packet_no_ack_res_send.clone(),
-                channel,
-                address,
-                payload,
+            .send(RadioCommand::WithRadio {
+                job: Box::new(move |cr| {
+                    // Ignore the error if the client has dropped since it did the request
+                    let _ = client.send(f(cr));
+                }),
             })
-            .unwrap();
-        Ok(())
+            .map_err(|_| Error::NotFound)?;
+
+        // The radio thread drops the closure without running it if it exits
+        result.recv().unwrap_or(Err(Error::NotFound))
+    }
+
+    fn configure(&mut self, setting: RadioSetting) -> Result<()> {
+        self.request(|client| RadioCommand::Configure { client, setting })
     }
 
     /// Create a weak reference to this SharedCrazyradio.
@@ -176,6 +346,20 @@ impl SharedCrazyradio {
 #[cfg(feature = "async")]
 #[cfg_attr(docsrs, doc(cfg(feature = "async")))]
 impl SharedCrazyradio {
+    // Async version of request()
+    async fn request_async<T>(
+        &self,
+        command: impl FnOnce(Sender<Result<T>>) -> RadioCommand,
+    ) -> Result<T> {
+        let (client, result) = bounded(1);
+        self.radio_command
+            .send_async(command(client))
+            .await
+            .unwrap();
+
+        result.recv_async().await.unwrap()
+    }
+
     /// Async version of `scan()`
     pub async fn scan_async(
         &mut self,
@@ -184,18 +368,33 @@ impl SharedCrazyradio {
         address: [u8; 5],
         payload: Vec<u8>,
     ) -> Result<Vec<Channel>> {
-        self.radio_command
-            .send_async(RadioCommand::Scan {
-                client: self.scan_res_send.clone(),
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.scan_cancellable_async(start, stop, address, payload, cancel)
+            .await
+    }
+
+    /// Async version of `scan_cancellable()`
+    ///
+    /// Dropping the returned future does not stop a scan already queued to
+    /// the radio thread, set `cancel` to stop it.
+    pub async fn scan_cancellable_async(
+        &mut self,
+        start: Channel,
+        stop: Channel,
+        address: [u8; 5],
+        payload: Vec<u8>,
+        cancel: Arc<AtomicBool>,
+    ) -> Result<Vec<Channel>> {
+        let result = self
+            .request_async(|client| RadioCommand::Scan {
+                client,
                 start,
                 stop,
                 address,
                 payload,
+                cancel,
             })
-            .await
-            .unwrap();
-
-        let result = self.scan_res.recv_async().await.unwrap()?;
+            .await?;
 
         Ok(result.found)
     }
@@ -207,28 +406,16 @@ impl SharedCrazyradio {
         address: [u8; 5],
         payload: Vec<u8>,
     ) -> Result<(Ack, Vec<u8>)> {
-        self.radio_command
-            .send_async(RadioCommand::SendPacket {
-                client: self.send_packet_res_send.clone(),
+        let result = self
+            .request_async(|client| RadioCommand::SendPacket {
+                client,
                 channel,
                 address,
                 payload,
             })
-            .await
-            .unwrap();
+            .await?;
 
-        let result = self.send_packet_res.recv_async().await.unwrap()?;
-
-        Ok((
-            Ack {
-                received: result.acked,
-                length: result.payload.len(),
-                power_detector: result.power_detector,
-                retry: result.retry,
-                rssi_dbm: result.rssi_dbm,
-            },
-            result.payload,
-        ))
+        Ok(result.into_ack())
     }
 
     /// Async version of `send_packet_no_ack()`
@@ -238,41 +425,111 @@ impl SharedCrazyradio {
         address: [u8; 5],
         payload: Vec<u8>,
     ) -> Result<()> {
-        self.radio_command
-            .send_async(RadioCommand::SendPacketNoAck {
-                client: self.send_packet_no_ack_res_send.clone(),
-                channel,
-                address,
-                payload,
-            })
+        self.request_async(|client| RadioCommand::SendPacketNoAck {
+            client,
+            channel,
+            address,
+            payload,
+        })
+        .await
+    }
+
+    /// Async version of `send_packet_no_ack_repeated()`
+    pub async fn send_packet_no_ack_repeated_async(
+        &mut self,
+        channel: Channel,
+        address: [u8; 5],
+        payload: Vec<u8>,
+        count: usize,
+        interval: Duration,
+    ) -> Result<()> {
+        self.request_async(|client| RadioCommand::SendPacketNoAckRepeated {
+            client,
+            channel,
+            address,
+            payload,
+            count,
+            interval,
+        })
+        .await
+    }
+
+    /// Async version of `set_datarate()`
+    ///
+    /// As for the other requests, the setting is applied by the radio thread
+    /// in order with the requests of the other users of the radio.
+    pub async fn set_datarate_async(&mut self, datarate: Datarate) -> Result<()> {
+        self.configure_async(RadioSetting::Datarate(datarate)).await
+    }
+
+    /// Async version of `set_power()`
+    pub async fn set_power_async(&mut self, power: Power) -> Result<()> {
+        self.configure_async(RadioSetting::Power(power)).await
+    }
+
+    /// Async version of `set_arc()`
+    pub async fn set_arc_async(&mut self, arc: usize) -> Result<()> {
+        self.configure_async(RadioSetting::Arc(arc)).await
+    }
+
+    /// Async version of `set_ard_time()`
+    pub async fn set_ard_time_async(&mut self, delay: Duration) -> Result<()> {
+        self.configure_async(RadioSetting::ArdTime(delay)).await
+    }
+
+    /// Async version of `set_ard_bytes()`
+    pub async fn set_ard_bytes_async(&mut self, nbytes: u8) -> Result<()> {
+        self.configure_async(RadioSetting::ArdBytes(nbytes)).await
+    }
+
+    async fn configure_async(&mut self, setting: RadioSetting) -> Result<()> {
+        self.request_async(|client| RadioCommand::Configure { client, setting })
             .await
-            .unwrap();
+    }
+}
+
+/// Packet queued by [SharedCrazyradio::try_send_packet()]
+pub struct PendingPacket {
+    result: Receiver<Result<SendPacketResult>>,
+    done: bool,
+}
 
-        self.send_packet_no_ack_res.recv_async().await.unwrap()?;
+impl PendingPacket {
+    /// Return the result of the send if the radio thread has sent the packet
+    ///
+    /// Returns `None` while the packet is pending. The result, the ack and its
+    /// payload, is returned only once: `None` is returned by the next calls.
+    /// Returns `Error::NotFound` if the radio thread has stopped.
+    pub fn poll(&mut self) -> Option<Result<(Ack, Vec<u8>)>> {
+        if self.done {
+            return None;
+        }
+
+        let result = match self.result.try_recv() {
+            Ok(result) => result.map(SendPacketResult::into_ack),
+            Err(flume::TryRecvError::Empty) => return None,
+            Err(flume::TryRecvError::Disconnected) => Err(Error::NotFound),
+        };
+        self.done = true;
+        Some(result)
+    }
+}
 
-        Ok(())
+impl PacketTransport for SharedCrazyradio {
+    fn send_packet(
+        &mut self,
+        channel: Channel,
+        address: [u8; 5],
+        payload: &[u8],
+    ) -> Result<(Ack, Vec<u8>)> {
+        SharedCrazyradio::send_packet(self, channel, address, payload.to_vec())
     }
 }
 
 impl Clone for SharedCrazyradio {
     fn clone(&self) -> Self {
-        // Create new pair of return channels
-        let (send_packet_res_send, send_packet_res) = bounded(1);
-        let (send_packet_no_ack_res_send, send_packet_no_ack_res) = bounded(1);
-        let (scan_res_send, scan_res) = bounded(1);
-
         // The command channel is cloned
-        let radio_command = self.radio_command.clone();
-
-        SharedCrazyradio {
-            radio_command,
-            send_packet_res_send,
-            send_packet_res,
-            send_packet_no_ack_res_send,
-            send_packet_no_ack_res,
-            scan_res_send,
-            scan_res,
-        }
+        SharedCrazyradio::from_command_sender(self.radio_command.clone())
     }
 }
 
@@ -282,18 +539,11 @@ impl Clone for SharedCrazyradio {
 ///
 /// This is useful to make sure the radio usb device is closed as soon as all
 /// `SharedCrazyradio` instances are dropped.
+#[derive(Default)]
 pub struct WeakSharedCrazyradio {
     radio_command: Option<WeakSender<RadioCommand>>,
 }
 
-impl Default for WeakSharedCrazyradio {
-    fn default() -> Self {
-        WeakSharedCrazyradio {
-            radio_command: None,
-        }
-    }
-}
-
 impl WeakSharedCrazyradio {
     /// Create a `SharedCrazyradio` from a weak reference.
     ///
@@ -302,20 +552,7 @@ impl WeakSharedCrazyradio {
     pub fn upgrade(&self) -> Option<SharedCrazyradio> {
         let radio_command = self.radio_command.as_ref()?.upgrade()?;
 
-        // Create new pair of return channels
-        let (send_packet_res_send, send_packet_res) = bounded(1);
-        let (send_packet_no_ack_res_send, send_packet_no_ack_res) = bounded(1);
-        let (scan_res_send, scan_res) = bounded(1);
-
-        Some(SharedCrazyradio {
-            radio_command,
-            send_packet_res_send,
-            send_packet_res,
-            send_packet_no_ack_res_send,
-            send_packet_no_ack_res,
-            scan_res_send,
-            scan_res,
-        })
+        Some(SharedCrazyradio::from_command_sender(radio_command))
     }
 }
 
@@ -332,13 +569,38 @@ enum RadioCommand {
         address: [u8; 5],
         payload: Vec<u8>,
     },
+    SendPacketNoAckRepeated {
+        client: Sender<Result<()>>,
+        channel: Channel,
+        address: [u8; 5],
+        payload: Vec<u8>,
+        count: usize,
+        interval: Duration,
+    },
     Scan {
         client: Sender<Result<ScanResult>>,
         start: Channel,
         stop: Channel,
         address: [u8; 5],
         payload: Vec<u8>,
+        cancel: Arc<AtomicBool>,
     },
+    Configure {
+        client: Sender<Result<()>>,
+        setting: RadioSetting,
+    },
+    WithRadio {
+        job: Box<dyn FnOnce(&mut Crazyradio) + Send>,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum RadioSetting {
+    Datarate(Datarate),
+    Power(Power),
+    Arc(usize),
+    ArdTime(Duration),
+    ArdBytes(u8),
 }
 
 struct SendPacketResult {
@@ -348,6 +610,21 @@ struct SendPacketResult {
     power_detector: bool,
     rssi_dbm: Option<i16>,
 }
+impl SendPacketResult {
+    fn into_ack(self) -> (Ack, Vec<u8>) {
+        (
+            Ack {
+                received: self.acked,
+                length: self.payload.len(),
+                power_detector: self.power_detector,
+                retry: self.retry,
+                rssi_dbm: self.rssi_dbm,
+            },
+            self.payload,
+        )
+    }
+}
+
 struct ScanResult {
     found: Vec<Channel>,
 }
@@ -357,10 +634,11 @@ fn scan(
     start: Channel,
     stop: Channel,
     address: [u8; 5],
-    payload: Vec<u8>,
+    payload: &[u8],
+    cancel: &AtomicBool,
 ) -> Result<ScanResult> {
     crazyradio.set_address(&address)?;
-    let found = crazyradio.scan_channels(start, stop, &payload)?;
+    let found = crazyradio.scan_channels_cancellable(start, stop, payload, cancel)?;
 
     Ok(ScanResult { found })
 }
@@ -369,15 +647,14 @@ fn send_packet(
     crazyradio: &mut Crazyradio,
     channel: Channel,
     address: [u8; 5],
-    payload: Vec<u8>,
+    payload: &[u8],
 ) -> Result<SendPacketResult> {
-    let mut ack_data = Vec::new();
-    ack_data.resize(32, 0);
+    let mut ack_data = vec![0; 32];
     crazyradio.set_channel(channel)?;
     crazyradio.set_address(&address)?;
     crazyradio.set_ack_enable(true)?;
 
-    let ack = crazyradio.send_packet(&payload, &mut ack_data)?;
+    let ack = crazyradio.send_packet(payload, &mut ack_data)?;
     ack_data.resize(ack.length, 0);
 
     Ok(SendPacketResult {
@@ -393,17 +670,97 @@ fn send_packet_no_ack(
     crazyradio: &mut Crazyradio,
     channel: Channel,
     address: [u8; 5],
-    payload: Vec<u8>,
+    payload: &[u8],
+) -> Result<()> {
+    crazyradio.set_channel(channel)?;
+    crazyradio.set_address(&address)?;
+    crazyradio.set_ack_enable(false)?;
+
+    crazyradio.send_packet_no_ack(payload)
+}
+
+fn send_packet_no_ack_repeated(
+    crazyradio: &mut Crazyradio,
+    channel: Channel,
+    address: [u8; 5],
+    payload: &[u8],
+    count: usize,
+    interval: Duration,
 ) -> Result<()> {
     crazyradio.set_channel(channel)?;
     crazyradio.set_address(&address)?;
     crazyradio.set_ack_enable(false)?;
 
-    crazyradio.send_packet_no_ack(&payload)
+    crazyradio.send_packet_no_ack_repeated(payload, count, interval)
+}
+
+fn configure(crazyradio: &mut Crazyradio, setting: RadioSetting) -> Result<()> {
+    match setting {
+        RadioSetting::Datarate(datarate) => crazyradio.set_datarate(datarate),
+        RadioSetting::Power(power) => crazyradio.set_power(power),
+        RadioSetting::Arc(arc) => crazyradio.set_arc(arc),
+        RadioSetting::ArdTime(delay) => crazyradio.set_ard_time(delay),
+        RadioSetting::ArdBytes(nbytes) => crazyradio.set_ard_bytes(nbytes),
+    }
+}
+
+// Reopen the radio by serial number after it has been disconnected
+struct Recovery {
+    serial: String,
+    backoff: Vec<Duration>,
+}
+
+impl Recovery {
+    // Open the radio again and restore the configuration of `previous`
+    fn reopen(&self, previous: &Crazyradio) -> Result<Crazyradio> {
+        let mut crazyradio = Crazyradio::open_by_serial(&self.serial)?;
+        crazyradio.reset_to(&previous.config())?;
+        Ok(crazyradio)
+    }
+}
+
+// Run `op` on `radio`. If it fails because the device is gone, try to
+// `reopen` the radio after each delay of `backoff` and run `op` again on the
+// new radio as soon as one attempt succeeds. Returns the original error if
+// all the attempts fail.
+fn with_recovery<R, T>(
+    radio: &mut R,
+    backoff: &[Duration],
+    mut reopen: impl FnMut(&R) -> Result<R>,
+    mut op: impl FnMut(&mut R) -> Result<T>,
+) -> Result<T> {
+    let error = match op(radio) {
+        Err(e @ Error::UsbError(rusb::Error::NoDevice)) => e,
+        result => return result,
+    };
+
+    for delay in backoff {
+        std::thread::sleep(*delay);
+        if let Ok(reopened) = reopen(radio) {
+            *radio = reopened;
+            return op(radio);
+        }
+    }
+
+    Err(error)
 }
 
-fn radio_loop(crazyradio: Crazyradio, radio_cmd: Receiver<RadioCommand>) {
+fn radio_loop(
+    crazyradio: Crazyradio,
+    radio_cmd: Receiver<RadioCommand>,
+    recovery: Option<Recovery>,
+) {
     let mut crazyradio = crazyradio;
+
+    let backoff = recovery
+        .as_ref()
+        .map(|recovery| recovery.backoff.as_slice())
+        .unwrap_or_default();
+    let reopen = |previous: &Crazyradio| match &recovery {
+        Some(recovery) => recovery.reopen(previous),
+        None => Err(Error::NotFound),
+    };
+
     for command in radio_cmd {
         match command {
             RadioCommand::Scan {
@@ -412,8 +769,11 @@ fn radio_loop(crazyradio: Crazyradio, radio_cmd: Receiver<RadioCommand>) {
                 stop,
                 address,
                 payload,
+                cancel,
             } => {
-                let res = scan(&mut crazyradio, start, stop, address, payload);
+                let res = with_recovery(&mut crazyradio, backoff, reopen, |cr| {
+                    scan(cr, start, stop, address, &payload, &cancel)
+                });
                 // Ignore the error if the client has dropped since it did the request
                 let _ = client.send(res);
             }
@@ -423,7 +783,9 @@ fn radio_loop(crazyradio: Crazyradio, radio_cmd: Receiver<RadioCommand>) {
                 address,
                 payload,
             } => {
-                let res = send_packet(&mut crazyradio, channel, address, payload);
+                let res = with_recovery(&mut crazyradio, backoff, reopen, |cr| {
+                    send_packet(cr, channel, address, &payload)
+                });
                 // Ignore the error if the client has dropped since it did the request
                 let _ = client.send(res);
             }
@@ -433,10 +795,285 @@ fn radio_loop(crazyradio: Crazyradio, radio_cmd: Receiver<RadioCommand>) {
                 address,
                 payload,
             } => {
-                let res = send_packet_no_ack(&mut crazyradio, channel, address, payload);
+                let res = with_recovery(&mut crazyradio, backoff, reopen, |cr| {
+                    send_packet_no_ack(cr, channel, address, &payload)
+                });
+                // Ignore the error if the client has dropped since it did the request
+                let _ = client.send(res);
+            }
+            RadioCommand::SendPacketNoAckRepeated {
+                client,
+                channel,
+                address,
+                payload,
+                count,
+                interval,
+            } => {
+                let res = with_recovery(&mut crazyradio, backoff, reopen, |cr| {
+                    send_packet_no_ack_repeated(cr, channel, address, &payload, count, interval)
+                });
+                // Ignore the error if the client has dropped since it did the request
+                let _ = client.send(res);
+            }
+            RadioCommand::Configure { client, setting } => {
+                let res = with_recovery(&mut crazyradio, backoff, reopen, |cr| {
+                    configure(cr, setting)
+                });
                 // Ignore the error if the client has dropped since it did the request
                 let _ = client.send(res);
             }
+            RadioCommand::WithRadio { job } => job(&mut crazyradio),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stand-in for radio_loop that answers every command without hardware.
+    // Send requests are acked with their own payload echoed back.
+    // The last datarate configured is reported as the retry count of the acks.
+    fn fake_radio_loop(radio_cmd: Receiver<RadioCommand>) {
+        let mut datarate = 0;
+        for command in radio_cmd {
+            match command {
+                RadioCommand::SendPacket {
+                    client, payload, ..
+                } => {
+                    let _ = client.send(Ok(SendPacketResult {
+                        acked: true,
+                        payload,
+                        retry: datarate,
+                        power_detector: false,
+                        rssi_dbm: None,
+                    }));
+                }
+                RadioCommand::SendPacketNoAck { client, .. }
+                | RadioCommand::SendPacketNoAckRepeated { client, .. } => {
+                    let _ = client.send(Ok(()));
+                }
+                RadioCommand::Scan { client, start, .. } => {
+                    let _ = client.send(Ok(ScanResult { found: vec![start] }));
+                }
+                RadioCommand::Configure { client, setting } => {
+                    if let RadioSetting::Datarate(dr) = setting {
+                        datarate = dr as usize;
+                    }
+                    let _ = client.send(Ok(()));
+                }
+                // There is no radio to run the closure on
+                RadioCommand::WithRadio { .. } => (),
+            }
+        }
+    }
+
+    fn fake_shared_radio() -> SharedCrazyradio {
+        let (radio_command, radio_command_recv) = unbounded();
+        std::thread::spawn(move || fake_radio_loop(radio_command_recv));
+        SharedCrazyradio::from_command_sender(radio_command)
+    }
+
+    #[test]
+    fn with_radio_fails_if_the_closure_is_not_run() {
+        let radio = fake_shared_radio();
+
+        let result = radio.with_radio(|cr| cr.serial());
+
+        assert!(matches!(result, Err(Error::NotFound)));
+    }
+
+    #[test]
+    fn with_radio_fails_if_the_radio_thread_has_stopped() {
+        let (radio_command, radio_command_recv) = unbounded();
+        let radio = SharedCrazyradio::from_command_sender(radio_command);
+        drop(radio_command_recv);
+
+        let result = radio.with_radio(|cr| cr.serial());
+
+        assert!(matches!(result, Err(Error::NotFound)));
+    }
+
+    #[test]
+    #[ignore = "needs a Crazyradio"]
+    fn with_radio_returns_the_result_of_the_closure() {
+        let radio = Crazyradio::open_first().unwrap();
+        let serial = radio.serial().unwrap();
+        let radio = SharedCrazyradio::new(radio);
+
+        assert_eq!(radio.with_radio(|cr| cr.serial()).unwrap(), serial);
+    }
+
+    #[test]
+    fn back_to_back_requests_on_one_handle_get_their_own_answer() {
+        let mut radio = fake_shared_radio();
+        let channel = Channel::from_number(42).unwrap();
+
+        radio
+            .send_packet_no_ack(channel, [0xe7; 5], vec![0xff])
+            .unwrap();
+        radio
+            .send_packet_no_ack(channel, [0xe7; 5], vec![0xff])
+            .unwrap();
+
+        let (_, first) = radio.send_packet(channel, [0xe7; 5], vec![1]).unwrap();
+        let (_, second) = radio.send_packet(channel, [0xe7; 5], vec![2]).unwrap();
+
+        assert_eq!(first, vec![1]);
+        assert_eq!(second, vec![2]);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn dropped_async_request_does_not_answer_the_next_one() {
+        use std::future::Future;
+        use std::task::{Context, Waker};
+
+        let (radio_command, radio_command_recv) = unbounded();
+        let mut radio = SharedCrazyradio::from_command_sender(radio_command);
+        let channel = Channel::from_number(42).unwrap();
+
+        // Queue a request and drop it before the radio thread answers
+        let mut dropped = Box::pin(radio.send_packet_async(channel, [0xe7; 5], vec![1]));
+        assert!(dropped
+            .as_mut()
+            .poll(&mut Context::from_waker(Waker::noop()))
+            .is_pending());
+        drop(dropped);
+
+        let Ok(RadioCommand::SendPacket {
+            client, payload, ..
+        }) = radio_command_recv.try_recv()
+        else {
+            panic!("Packet not queued");
+        };
+        let _ = client.send(Ok(SendPacketResult {
+            acked: true,
+            payload,
+            retry: 0,
+            power_detector: false,
+            rssi_dbm: None,
+        }));
+
+        std::thread::spawn(move || fake_radio_loop(radio_command_recv));
+        let (_, payload) = radio.send_packet(channel, [0xe7; 5], vec![2]).unwrap();
+
+        assert_eq!(payload, vec![2]);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn settings_apply_to_the_following_async_sends() {
+        let mut radio = fake_shared_radio();
+        let channel = Channel::from_number(42).unwrap();
+
+        radio.set_datarate_async(Datarate::Dr1M).await.unwrap();
+        let (ack, _) = radio
+            .send_packet_async(channel, [0xe7; 5], vec![0xff])
+            .await
+            .unwrap();
+
+        assert_eq!(ack.retry, Datarate::Dr1M as usize);
+    }
+
+    fn ping(transport: &mut impl PacketTransport) -> Result<Vec<u8>> {
+        let channel = Channel::from_number(42).unwrap();
+        let (_, payload) = transport.send_packet(channel, [0xe7; 5], &[0xff])?;
+        Ok(payload)
+    }
+
+    #[test]
+    fn generic_code_runs_on_any_packet_transport() {
+        // Exclusive radios are transports as well, they need hardware to run
+        let _: fn(&mut Crazyradio) -> Result<Vec<u8>> = ping;
+
+        assert_eq!(ping(&mut fake_shared_radio()).unwrap(), vec![0xff]);
+    }
+
+    #[test]
+    fn pending_packet_is_ready_once_the_radio_thread_replies() {
+        let (radio_command, radio_command_recv) = unbounded();
+        let radio = SharedCrazyradio::from_command_sender(radio_command);
+        let channel = Channel::from_number(42).unwrap();
+
+        let mut pending = radio
+            .try_send_packet(channel, [0xe7; 5], vec![0xff])
+            .unwrap();
+        assert!(pending.poll().is_none());
+
+        let Ok(RadioCommand::SendPacket {
+            client, payload, ..
+        }) = radio_command_recv.try_recv()
+        else {
+            panic!("Packet not queued");
+        };
+        client
+            .send(Ok(SendPacketResult {
+                acked: true,
+                payload,
+                retry: 0,
+                power_detector: false,
+                rssi_dbm: None,
+            }))
+            .unwrap();
+
+        let (ack, payload) = pending.poll().unwrap().unwrap();
+        assert!(ack.received);
+        assert_eq!(payload, vec![0xff]);
+        assert!(pending.poll().is_none());
+    }
+
+    #[test]
+    fn try_send_packet_fails_if_the_radio_thread_has_stopped() {
+        let (radio_command, radio_command_recv) = unbounded();
+        let radio = SharedCrazyradio::from_command_sender(radio_command);
+        drop(radio_command_recv);
+
+        let result = radio.try_send_packet(Channel::from_number(42).unwrap(), [0xe7; 5], vec![]);
+
+        assert!(matches!(result, Err(Error::NotFound)));
+    }
+
+    #[test]
+    fn disconnected_radio_is_reopened_and_the_request_run_again() {
+        // The radio is a generation number, generation 0 is disconnected
+        let mut radio = 0;
+        let mut reopen_attempts = 0;
+        let backoff = [Duration::ZERO; 3];
+
+        let result = with_recovery(
+            &mut radio,
+            &backoff,
+            |previous| {
+                reopen_attempts += 1;
+                if reopen_attempts < 2 {
+                    Err(Error::NotFound)
+                } else {
+                    Ok(previous + 1)
+                }
+            },
+            |radio| match radio {
+                0 => Err(Error::UsbError(rusb::Error::NoDevice)),
+                generation => Ok(*generation),
+            },
+        );
+
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(reopen_attempts, 2);
+        assert_eq!(radio, 1);
+    }
+
+    #[test]
+    fn other_errors_are_not_recovered() {
+        let mut radio = 0;
+
+        let result: Result<()> = with_recovery(
+            &mut radio,
+            &[Duration::ZERO],
+            |_| panic!("should not reopen"),
+            |_| Err(Error::UsbError(rusb::Error::Timeout)),
+        );
+
+        assert!(matches!(result, Err(Error::UsbError(rusb::Error::Timeout))));
+    }
+}
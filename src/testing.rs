@@ -0,0 +1,292 @@
+//! In-memory fake Crazyradio to test code using the radio without hardware
+//!
+//! [FakeRadio] implements the packet and configuration methods of
+//! [Crazyradio](crate::Crazyradio) with the same signatures, as well as
+//! [PacketTransport]. Acks are scripted in advance with
+//! [FakeRadio::push_ack()] and every call is recorded so that a test can
+//! assert the sequence of commands issued to the radio.
+//!
+//! Example:
+//! ```
+//! use crazyradio::testing::{FakeCommand, FakeRadio};
+//! use crazyradio::Channel;
+//!
+//! # fn main() -> Result<(), crazyradio::Error> {
+//! let mut radio = FakeRadio::new();
+//! radio.push_ack(&[0x01]);
+//! radio.push_ack(&[0x02, 0x03]);
+//!
+//! radio.set_channel(Channel::from_number(42)?)?;
+//!
+//! let mut ack_data = [0u8; 32];
+//! let ack = radio.send_packet(&[0xff], &mut ack_data)?;
+//! assert_eq!(&ack_data[..ack.length], &[0x01]);
+//! let ack = radio.send_packet(&[0xff], &mut ack_data)?;
+//! assert_eq!(&ack_data[..ack.length], &[0x02, 0x03]);
+//!
+//! assert_eq!(
+//!     radio.commands(),
+//!     &[
+//!         FakeCommand::SetChannel(Channel::from_number(42)?),
+//!         FakeCommand::SendPacket(vec![0xff]),
+//!         FakeCommand::SendPacket(vec![0xff]),
+//!     ]
+//! );
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+
+use crate::{Ack, Channel, Datarate, PacketTransport, Power, Result};
+
+/// Command issued to a [FakeRadio]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FakeCommand {
+    /// [FakeRadio::set_channel()] has been called
+    SetChannel(Channel),
+    /// [FakeRadio::set_datarate()] has been called
+    SetDatarate(Datarate),
+    /// [FakeRadio::set_address()] has been called
+    SetAddress([u8; 5]),
+    /// [FakeRadio::set_power()] has been called
+    SetPower(Power),
+    /// [FakeRadio::set_arc()] has been called
+    SetArc(usize),
+    /// [FakeRadio::set_ack_enable()] has been called
+    SetAckEnable(bool),
+    /// [FakeRadio::send_packet()] has been called with this data
+    SendPacket(Vec<u8>),
+    /// [FakeRadio::send_packet_no_ack()] has been called with this data
+    SendPacketNoAck(Vec<u8>),
+    /// [FakeRadio::set_packet_loss_simulation()] has been called
+    SetPacketLossSimulation(u8, u8),
+}
+
+/// Scriptable fake Crazyradio, see the [module documentation](self)
+#[derive(Debug, Default)]
+pub struct FakeRadio {
+    acks: VecDeque<Option<Vec<u8>>>,
+    commands: Vec<FakeCommand>,
+    packet_loss: (u8, u8),
+}
+
+impl FakeRadio {
+    /// Create a fake radio with no scripted acks
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue an ack carrying `payload` as answer to a future send_packet()
+    pub fn push_ack(&mut self, payload: &[u8]) {
+        self.acks.push_back(Some(payload.to_vec()));
+    }
+
+    /// Queue a lost packet: a future send_packet() will not receive any ack
+    pub fn push_no_ack(&mut self) {
+        self.acks.push_back(None);
+    }
+
+    /// Commands issued to the radio since it was created, oldest first
+    pub fn commands(&self) -> &[FakeCommand] {
+        &self.commands
+    }
+
+    /// Same as [Crazyradio::set_channel()](crate::Crazyradio::set_channel())
+    pub fn set_channel(&mut self, channel: Channel) -> Result<()> {
+        self.commands.push(FakeCommand::SetChannel(channel));
+        Ok(())
+    }
+
+    /// Same as [Crazyradio::set_datarate()](crate::Crazyradio::set_datarate())
+    pub fn set_datarate(&mut self, datarate: Datarate) -> Result<()> {
+        self.commands.push(FakeCommand::SetDatarate(datarate));
+        Ok(())
+    }
+
+    /// Same as [Crazyradio::set_address()](crate::Crazyradio::set_address())
+    pub fn set_address(&mut self, address: &[u8; 5]) -> Result<()> {
+        self.commands.push(FakeCommand::SetAddress(*address));
+        Ok(())
+    }
+
+    /// Same as [Crazyradio::set_power()](crate::Crazyradio::set_power())
+    pub fn set_power(&mut self, power: Power) -> Result<()> {
+        self.commands.push(FakeCommand::SetPower(power));
+        Ok(())
+    }
+
+    /// Same as [Crazyradio::set_arc()](crate::Crazyradio::set_arc())
+    pub fn set_arc(&mut self, arc: usize) -> Result<()> {
+        self.commands.push(FakeCommand::SetArc(arc));
+        Ok(())
+    }
+
+    /// Same as [Crazyradio::set_ack_enable()](crate::Crazyradio::set_ack_enable())
+    pub fn set_ack_enable(&mut self, ack_enable: bool) -> Result<()> {
+        self.commands.push(FakeCommand::SetAckEnable(ack_enable));
+        Ok(())
+    }
+
+    /// Same as [Crazyradio::set_packet_loss_simulation()](crate::Crazyradio::set_packet_loss_simulation())
+    ///
+    /// Only the configuration is recorded, no packet or ack is dropped.
+    pub fn set_packet_loss_simulation(
+        &mut self,
+        packet_loss_percent: u8,
+        ack_loss_percent: u8,
+    ) -> Result<()> {
+        let commands = &mut self.commands;
+        crate::set_packet_loss_with(
+            &mut self.packet_loss,
+            packet_loss_percent,
+            ack_loss_percent,
+            |[packet_loss, ack_loss]| {
+                commands.push(FakeCommand::SetPacketLossSimulation(packet_loss, ack_loss));
+                Ok(())
+            },
+        )
+    }
+
+    /// Same as [Crazyradio::packet_loss_simulation()](crate::Crazyradio::packet_loss_simulation())
+    pub fn packet_loss_simulation(&self) -> (u8, u8) {
+        self.packet_loss
+    }
+
+    /// Same as [Crazyradio::clear_packet_loss_simulation()](crate::Crazyradio::clear_packet_loss_simulation())
+    pub fn clear_packet_loss_simulation(&mut self) -> Result<()> {
+        self.set_packet_loss_simulation(0, 0)
+    }
+
+    /// Same as [Crazyradio::send_packet()](crate::Crazyradio::send_packet())
+    ///
+    /// Answers with the next scripted ack. If no ack is scripted, the packet
+    /// is reported as not acked. Empty packets are rejected and packets longer
+    /// than 32 bytes panic in debug builds, like with the Crazyradio.
+    pub fn send_packet(&mut self, data: &[u8], ack_data: &mut [u8]) -> Result<Ack> {
+        crate::debug_assert_payload_length(data);
+        crate::check_packet_data(data)?;
+        self.commands.push(FakeCommand::SendPacket(data.to_vec()));
+
+        let payload = self.acks.pop_front().flatten();
+        let length = match &payload {
+            Some(payload) => {
+                let length = payload.len().min(ack_data.len());
+                ack_data[..length].copy_from_slice(&payload[..length]);
+                payload.len()
+            }
+            None => 0,
+        };
+
+        Ok(Ack {
+            received: payload.is_some(),
+            power_detector: false,
+            retry: 0,
+            length,
+            rssi_dbm: None,
+        })
+    }
+
+    /// Same as [Crazyradio::ping()](crate::Crazyradio::ping())
+    ///
+    /// Sends `[0xff]` with [FakeRadio::send_packet()], answered by the next
+    /// scripted ack.
+    pub fn ping(&mut self) -> Result<bool> {
+        crate::ping_with(|data, ack_data| self.send_packet(data, ack_data))
+    }
+
+    /// Same as [Crazyradio::send_packet_no_ack()](crate::Crazyradio::send_packet_no_ack())
+    pub fn send_packet_no_ack(&mut self, data: &[u8]) -> Result<()> {
+        crate::debug_assert_payload_length(data);
+        self.commands
+            .push(FakeCommand::SendPacketNoAck(data.to_vec()));
+        Ok(())
+    }
+}
+
+impl PacketTransport for FakeRadio {
+    fn send_packet(
+        &mut self,
+        channel: Channel,
+        address: [u8; 5],
+        payload: &[u8],
+    ) -> Result<(Ack, Vec<u8>)> {
+        self.set_channel(channel)?;
+        self.set_address(&address)?;
+        self.set_ack_enable(true)?;
+
+        let mut ack_data = [0u8; 32];
+        let ack = FakeRadio::send_packet(self, payload, &mut ack_data)?;
+        let length = ack.length.min(ack_data.len());
+        Ok((ack, ack_data[..length].to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unscripted_send_is_not_acked() {
+        let mut radio = FakeRadio::new();
+        radio.push_no_ack();
+        let mut ack_data = [0u8; 32];
+
+        assert!(!radio.send_packet(&[0xff], &mut ack_data).unwrap().received);
+        assert!(!radio.send_packet(&[0xff], &mut ack_data).unwrap().received);
+        assert_eq!(radio.commands().len(), 2);
+    }
+
+    #[test]
+    fn transport_configures_the_link_before_sending() {
+        let mut radio = FakeRadio::new();
+        radio.push_ack(&[0x01, 0x02]);
+        let channel = Channel::from_number(42).unwrap();
+
+        let (ack, payload) =
+            PacketTransport::send_packet(&mut radio, channel, [0xe7; 5], &[0xff]).unwrap();
+
+        assert!(ack.received);
+        assert_eq!(payload, [0x01, 0x02]);
+        assert_eq!(
+            radio.commands(),
+            &[
+                FakeCommand::SetChannel(channel),
+                FakeCommand::SetAddress([0xe7; 5]),
+                FakeCommand::SetAckEnable(true),
+                FakeCommand::SendPacket(vec![0xff]),
+            ]
+        );
+    }
+
+    #[test]
+    fn ping_reports_the_ack_status() {
+        let mut radio = FakeRadio::new();
+        radio.push_ack(&[]);
+        radio.push_no_ack();
+
+        assert!(radio.ping().unwrap());
+        assert!(!radio.ping().unwrap());
+        assert_eq!(
+            radio.commands(),
+            &[
+                FakeCommand::SendPacket(vec![0xff]),
+                FakeCommand::SendPacket(vec![0xff]),
+            ]
+        );
+    }
+
+    #[test]
+    fn packet_loss_simulation_reads_back_and_clears() {
+        let mut radio = FakeRadio::new();
+
+        radio.set_packet_loss_simulation(10, 20).unwrap();
+        assert_eq!(radio.packet_loss_simulation(), (10, 20));
+
+        assert!(radio.set_packet_loss_simulation(101, 0).is_err());
+        assert_eq!(radio.packet_loss_simulation(), (10, 20));
+
+        radio.clear_packet_loss_simulation().unwrap();
+        assert_eq!(radio.packet_loss_simulation(), (0, 0));
+    }
+}
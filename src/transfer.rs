@@ -0,0 +1,279 @@
+//! Chunked data transfer over a radio link
+//!
+//! Helpers to send data larger than one radio packet, built on top of any
+//! [PacketTransport]. [send_bytes()] only sends raw data while
+//! [send_message()] frames the data and reassembles the answer of the receiver.
+//!
+//! Example:
+//! ```no_run
+//! # fn main() -> Result<(), crazyradio::Error> {
+//! let mut cr = crazyradio::Crazyradio::open_first()?;
+//! let channel = crazyradio::Channel::from_number(42)?;
+//! let data = [0x42; 100];
+//!
+//! crazyradio::transfer::send_bytes(&mut cr, channel, [0xe7; 5], &data, 32)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Channel, Error, PacketTransport, Result};
+
+/// Number of times a chunk is sent before giving up if it is not acked
+pub const MAX_CHUNK_ATTEMPTS: usize = 10;
+
+/// Longest message that can be sent with [send_message()]
+pub const MAX_MESSAGE_LENGTH: usize = 255 * FRAGMENT_DATA_LENGTH - 2;
+
+// Message bytes carried by one fragment, after the sequence number
+const FRAGMENT_DATA_LENGTH: usize = 31;
+// Sequence number of the packets sent to poll the answer
+const POLL_SEQUENCE: u8 = 0xff;
+
+/// Send `data` to `address` on `channel` in packets of up to `chunk_size` bytes
+///
+/// Each packet is sent until it is acked before sending the next one. If a
+/// packet is still not acked after [MAX_CHUNK_ATTEMPTS] attempts, the
+/// transfer stops and `Error::Timeout` is returned.
+///
+/// The packets only carry the data, framing the transfer (length, sequence
+/// number, ...) is left to the protocol of the receiver.
+///
+/// Returns `Error::InvalidArgument` if `chunk_size` is not between 1 and 32.
+pub fn send_bytes(
+    transport: &mut impl PacketTransport,
+    channel: Channel,
+    address: [u8; 5],
+    data: &[u8],
+    chunk_size: usize,
+) -> Result<()> {
+    if !(1..=32).contains(&chunk_size) {
+        return Err(Error::InvalidArgument);
+    }
+
+    for chunk in data.chunks(chunk_size) {
+        send_chunk(transport, channel, address, chunk)?;
+    }
+
+    Ok(())
+}
+
+/// Send `message` to `address` on `channel` and return the answer of the receiver
+///
+/// The message is prefixed with its length as a little-endian u16 and split
+/// in fragments of up to 32 bytes. Each fragment starts with a sequence
+/// number counting from 0, followed by up to 31 bytes of the prefixed message:
+///
+/// ```text
+/// fragment 0: [0, length_lo, length_hi, message[0..29]]
+/// fragment 1: [1, message[29..60]]
+/// ...
+/// ```
+///
+/// The answer is framed the same way by the receiver and sent back in the
+/// ack payloads. Once the message is sent, `[0xff]` poll packets are sent
+/// until the answer is complete. Fragments that do not carry the next
+/// expected sequence number, like repeated fragments, are ignored.
+///
+/// Each fragment is sent until it is acked, as with [send_bytes()]. The
+/// transfer fails with `Error::Timeout` if a fragment is never acked or if
+/// [MAX_CHUNK_ATTEMPTS] polls in a row do not bring any new part of the answer.
+///
+/// Returns `Error::InvalidArgument` if `message` is longer than [MAX_MESSAGE_LENGTH].
+pub fn send_message(
+    transport: &mut impl PacketTransport,
+    channel: Channel,
+    address: [u8; 5],
+    message: &[u8],
+) -> Result<Vec<u8>> {
+    if message.len() > MAX_MESSAGE_LENGTH {
+        return Err(Error::InvalidArgument);
+    }
+
+    let mut answer = Reassembly::default();
+    for fragment in fragments(message) {
+        answer.push(&send_chunk(transport, channel, address, &fragment)?);
+    }
+
+    let mut idle_polls = 0;
+    while !answer.is_complete() {
+        let ack_payload = send_chunk(transport, channel, address, &[POLL_SEQUENCE])?;
+        if answer.push(&ack_payload) {
+            idle_polls = 0;
+        } else {
+            idle_polls += 1;
+            if idle_polls >= MAX_CHUNK_ATTEMPTS {
+                return Err(Error::Timeout);
+            }
+        }
+    }
+
+    Ok(answer.into_message())
+}
+
+// Split `message` in length-prefixed and numbered fragments
+fn fragments(message: &[u8]) -> Vec<Vec<u8>> {
+    let mut framed = (message.len() as u16).to_le_bytes().to_vec();
+    framed.extend_from_slice(message);
+
+    framed
+        .chunks(FRAGMENT_DATA_LENGTH)
+        .enumerate()
+        .map(|(sequence, data)| {
+            let mut fragment = vec![sequence as u8];
+            fragment.extend_from_slice(data);
+            fragment
+        })
+        .collect()
+}
+
+// Message being rebuilt from its fragments
+#[derive(Default)]
+struct Reassembly {
+    next_sequence: u8,
+    data: Vec<u8>,
+}
+
+impl Reassembly {
+    // Returns true if the fragment was the next one expected
+    fn push(&mut self, fragment: &[u8]) -> bool {
+        match fragment.split_first() {
+            Some((&sequence, data)) if sequence == self.next_sequence => {
+                self.data.extend_from_slice(data);
+                self.next_sequence = self.next_sequence.wrapping_add(1);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn length(&self) -> Option<usize> {
+        self.data
+            .get(..2)
+            .map(|length| u16::from_le_bytes([length[0], length[1]]) as usize)
+    }
+
+    fn is_complete(&self) -> bool {
+        matches!(self.length(), Some(length) if self.data.len() >= length + 2)
+    }
+
+    fn into_message(mut self) -> Vec<u8> {
+        let length = self.length().unwrap_or(0);
+        self.data.drain(..2);
+        self.data.truncate(length);
+        self.data
+    }
+}
+
+// Send `chunk` until it is acked and return the ack payload
+fn send_chunk(
+    transport: &mut impl PacketTransport,
+    channel: Channel,
+    address: [u8; 5],
+    chunk: &[u8],
+) -> Result<Vec<u8>> {
+    for _ in 0..MAX_CHUNK_ATTEMPTS {
+        let (ack, payload) = transport.send_packet(channel, address, chunk)?;
+        if ack.received {
+            return Ok(payload);
+        }
+    }
+    Err(Error::Timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ack;
+    use std::collections::VecDeque;
+
+    // Records the packets sent, the packets listed in `drop` are not acked
+    struct DroppingTransport {
+        sent: Vec<Vec<u8>>,
+        drop: Vec<usize>,
+    }
+
+    impl PacketTransport for DroppingTransport {
+        fn send_packet(
+            &mut self,
+            _channel: Channel,
+            _address: [u8; 5],
+            payload: &[u8],
+        ) -> Result<(Ack, Vec<u8>)> {
+            let received = !self.drop.contains(&self.sent.len());
+            self.sent.push(payload.to_vec());
+            let ack = Ack {
+                received,
+                power_detector: false,
+                retry: 0,
+                length: 0,
+                rssi_dbm: None,
+            };
+            Ok((ack, vec![]))
+        }
+    }
+
+    // Receiver answering each message with the message reversed
+    #[derive(Default)]
+    struct ReversingPeer {
+        request: Reassembly,
+        answer: VecDeque<Vec<u8>>,
+    }
+
+    impl PacketTransport for ReversingPeer {
+        fn send_packet(
+            &mut self,
+            _channel: Channel,
+            _address: [u8; 5],
+            payload: &[u8],
+        ) -> Result<(Ack, Vec<u8>)> {
+            if payload[0] != POLL_SEQUENCE {
+                self.request.push(payload);
+                if self.request.is_complete() {
+                    let mut message = std::mem::take(&mut self.request).into_message();
+                    message.reverse();
+                    self.answer = fragments(&message).into();
+                }
+            }
+            let ack_payload = self.answer.pop_front().unwrap_or_default();
+            let ack = Ack {
+                received: true,
+                power_detector: false,
+                retry: 0,
+                length: ack_payload.len(),
+                rssi_dbm: None,
+            };
+            Ok((ack, ack_payload))
+        }
+    }
+
+    #[test]
+    fn message_round_trips_through_fragments() {
+        let mut peer = ReversingPeer::default();
+        let message: Vec<u8> = (0..70).collect();
+        let channel = Channel::from_number(42).unwrap();
+
+        let answer = send_message(&mut peer, channel, [0xe7; 5], &message).unwrap();
+
+        let mut expected = message;
+        expected.reverse();
+        assert_eq!(answer, expected);
+    }
+
+    #[test]
+    fn data_is_fragmented_and_dropped_chunks_sent_again() {
+        let mut transport = DroppingTransport {
+            sent: vec![],
+            drop: vec![1],
+        };
+        let data: Vec<u8> = (0..100).collect();
+        let channel = Channel::from_number(42).unwrap();
+
+        send_bytes(&mut transport, channel, [0xe7; 5], &data, 32).unwrap();
+
+        let lengths: Vec<_> = transport.sent.iter().map(|p| p.len()).collect();
+        assert_eq!(lengths, [32, 32, 32, 32, 4]);
+        assert_eq!(transport.sent[1], transport.sent[2]);
+        transport.sent.remove(1);
+        assert_eq!(transport.sent.concat(), data);
+    }
+}
@@ -0,0 +1,147 @@
+// USB access to a Crazyradio dongle
+//
+// The radio functions only reach the dongle through the UsbDevice trait. It is
+// implemented by the libusb device handle and, in the unit tests, by a fake
+// dongle emulating the firmware so that the radio functions can be tested
+// without hardware.
+
+use std::time::Duration;
+
+use crate::{device_max_packet_size, get_serial, interface_settings, Result};
+
+// Operations done on the claimed USB interface of a dongle, with the
+// signatures of rusb::DeviceHandle
+pub(crate) trait UsbDevice: Send + Sync {
+    fn write_control(
+        &self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buf: &[u8],
+        timeout: Duration,
+    ) -> rusb::Result<usize>;
+
+    fn read_control(
+        &self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> rusb::Result<usize>;
+
+    fn write_bulk(&self, endpoint: u8, buf: &[u8], timeout: Duration) -> rusb::Result<usize>;
+
+    fn read_bulk(&self, endpoint: u8, buf: &mut [u8], timeout: Duration) -> rusb::Result<usize>;
+
+    fn clear_halt(&self, endpoint: u8) -> rusb::Result<()>;
+
+    fn active_configuration(&self) -> rusb::Result<u8>;
+
+    fn set_active_configuration(&self, config: u8) -> rusb::Result<()>;
+
+    fn claim_interface(&self, interface: u8) -> rusb::Result<()>;
+
+    fn release_interface(&self, interface: u8) -> rusb::Result<()>;
+
+    fn set_alternate_setting(&self, interface: u8, setting: u8) -> rusb::Result<()>;
+
+    fn reset(&self) -> rusb::Result<()>;
+
+    // Information read from the device and its descriptors
+
+    fn serial(&self) -> Result<String>;
+
+    fn speed(&self) -> rusb::Speed;
+
+    fn firmware_version(&self) -> Result<rusb::Version>;
+
+    fn max_packet_size(&self, endpoint: u8) -> Result<u16>;
+
+    // (interface number, alternate setting) pairs of the active configuration
+    fn interface_settings(&self) -> Result<Vec<(u8, u8)>>;
+}
+
+impl<C: rusb::UsbContext> UsbDevice for rusb::DeviceHandle<C> {
+    fn write_control(
+        &self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buf: &[u8],
+        timeout: Duration,
+    ) -> rusb::Result<usize> {
+        self.write_control(request_type, request, value, index, buf, timeout)
+    }
+
+    fn read_control(
+        &self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> rusb::Result<usize> {
+        self.read_control(request_type, request, value, index, buf, timeout)
+    }
+
+    fn write_bulk(&self, endpoint: u8, buf: &[u8], timeout: Duration) -> rusb::Result<usize> {
+        self.write_bulk(endpoint, buf, timeout)
+    }
+
+    fn read_bulk(&self, endpoint: u8, buf: &mut [u8], timeout: Duration) -> rusb::Result<usize> {
+        self.read_bulk(endpoint, buf, timeout)
+    }
+
+    fn clear_halt(&self, endpoint: u8) -> rusb::Result<()> {
+        self.clear_halt(endpoint)
+    }
+
+    fn active_configuration(&self) -> rusb::Result<u8> {
+        self.active_configuration()
+    }
+
+    fn set_active_configuration(&self, config: u8) -> rusb::Result<()> {
+        self.set_active_configuration(config)
+    }
+
+    fn claim_interface(&self, interface: u8) -> rusb::Result<()> {
+        self.claim_interface(interface)
+    }
+
+    fn release_interface(&self, interface: u8) -> rusb::Result<()> {
+        self.release_interface(interface)
+    }
+
+    fn set_alternate_setting(&self, interface: u8, setting: u8) -> rusb::Result<()> {
+        self.set_alternate_setting(interface, setting)
+    }
+
+    fn reset(&self) -> rusb::Result<()> {
+        self.reset()
+    }
+
+    fn serial(&self) -> Result<String> {
+        get_serial(&self.device().device_descriptor()?, self)
+    }
+
+    fn speed(&self) -> rusb::Speed {
+        self.device().speed()
+    }
+
+    fn firmware_version(&self) -> Result<rusb::Version> {
+        Ok(self.device().device_descriptor()?.device_version())
+    }
+
+    fn max_packet_size(&self, endpoint: u8) -> Result<u16> {
+        device_max_packet_size(&self.device(), endpoint)
+    }
+
+    fn interface_settings(&self) -> Result<Vec<(u8, u8)>> {
+        interface_settings(&self.device())
+    }
+}